@@ -1,10 +1,24 @@
-use rust_sc2::{action::Target, ids::AbilityId, prelude::Point2, unit::Unit};
+use std::collections::HashMap;
+
+use rust_sc2::{
+    action::Target,
+    ids::{AbilityId, UnitTypeId},
+    prelude::Point2,
+    unit::Unit,
+};
 
 use crate::{
-    assignment_manager::{AssignmentManager, Identity},
+    assignment_manager::Identity,
+    build_orders::{BuildOrderAction, MapTarget},
+    build_tree::BuildComponent,
     protoss_bot::ReBiCycler,
 };
 
+/// expansions closer than this to a start location are that main
+const MAIN_BASE_RADIUS: f32 = 10.0;
+/// a scout this close to where it's going has seen what it went for
+const SCOUT_ARRIVED_DISTANCE: f32 = 7.0;
+
 impl ReBiCycler {
     pub fn update_army_states(&mut self) {
         let new_states: Vec<UnitState> = self
@@ -43,6 +57,69 @@ impl ReBiCycler {
     pub fn new_mission(&mut self, mission: MissionType, rally_point: Point2) -> usize {
         self.army_manager.add_mission(mission, rally_point)
     }
+
+    /// Where a build's named place is on this map
+    pub fn map_target(&self, target: MapTarget) -> Point2 {
+        let natural = |main: Point2| {
+            crate::closest_point(
+                main,
+                self.expansions
+                    .iter()
+                    .map(|e| e.loc)
+                    .filter(|loc| loc.distance(main) > MAIN_BASE_RADIUS),
+            )
+            .unwrap_or(main)
+        };
+        match target {
+            MapTarget::OurMain => self.start_location,
+            MapTarget::OurNatural => natural(self.start_location),
+            MapTarget::EnemyMain => self.enemy_start,
+            MapTarget::EnemyNatural => natural(self.enemy_start),
+            MapTarget::MapCenter => self.game_info.map_center,
+        }
+    }
+
+    /// Joins an unfinished mission of the same type, or starts one,
+    /// then pulls every fighting unit that isn't busy elsewhere into it.
+    pub fn launch_mission(&mut self, mission: MissionType, rally_point: Point2) -> usize {
+        let id = self
+            .army_manager
+            .find_mission(&mission)
+            .unwrap_or_else(|| self.new_mission(mission, rally_point));
+        self.army_manager.gather_army(id);
+        id
+    }
+
+    /// Ends scouting missions once no active build node asks for that scout any more,
+    /// or once the scout gets where it was going, and sends workers back to mining.
+    pub fn end_scouting(&mut self, active: &[BuildComponent]) {
+        let wanted: Vec<UnitTypeId> = active
+            .iter()
+            .filter_map(|component| match component.action() {
+                Some(BuildOrderAction::SendScout(unit_type)) => Some(unit_type),
+                _ => None,
+            })
+            .collect();
+        let finished = self.army_manager.finished_scouting(&wanted, |tag, target| {
+            self.units
+                .my
+                .units
+                .get(tag)
+                .is_some_and(|u| u.distance(target) < SCOUT_ARRIVED_DISTANCE)
+        });
+        for mission in finished {
+            for tag in self.army_manager.finish_mission(mission) {
+                if self.units.my.workers.contains_tag(tag) {
+                    self.back_to_work(tag);
+                }
+            }
+        }
+    }
+
+    /// new fighting units join the fight that's going on, if there is one
+    pub fn join_fight(&mut self, unit: &Unit) {
+        let _ = self.army_manager.join_fight(UnitState::from_unit(unit));
+    }
 }
 
 type Command = (u64, AbilityId, Target, bool);
@@ -109,15 +186,24 @@ impl Mission {
                     )
                 }
             }
-            MissionType::DetectArea(point) => (
+            MissionType::DetectArea(point) | MissionType::DefendBase(point) => (
                 unit.tag,
                 AbilityId::AttackAttackTowards,
                 Target::Pos(point),
                 false,
             ),
+            MissionType::Scout(point) => (unit.tag, AbilityId::MoveMove, Target::Pos(point), false),
         }
     }
 
+    /// fighting missions trade units between each other, the rest keep theirs
+    const fn is_fight(&self) -> bool {
+        matches!(
+            self.mission_type,
+            MissionType::AttackEnemy(_) | MissionType::DefendBase(_)
+        )
+    }
+
     const fn needs(&self, unit: &UnitState) -> bool {
         let needs_detector = matches!(self.mission_type, MissionType::DetectArea(_));
 
@@ -130,23 +216,33 @@ impl Mission {
 
 #[derive(Debug)]
 pub struct ArmyIssue;
+#[derive(Clone)]
 pub struct UnitState {
     is_detector: bool,
     tag: u64,
+    pub unit_type: UnitTypeId,
 }
 impl UnitState {
-    fn from_unit(_: &Unit) -> Self {
+    pub fn from_unit(unit: &Unit) -> Self {
         Self {
-            is_detector: false,
-            tag: 10,
+            is_detector: unit.is_detector(),
+            tag: unit.tag(),
+            unit_type: unit.type_id(),
         }
     }
 }
-#[derive(Clone, Hash)]
+impl Identity<u64> for UnitState {
+    fn id(&self) -> u64 {
+        self.tag
+    }
+}
+#[derive(Clone, Hash, PartialEq)]
 pub enum MissionType {
     BabysitConstruction(Point2),
     DetectArea(Point2),
     AttackEnemy(Point2),
+    DefendBase(Point2),
+    Scout(Point2),
 }
 
 #[derive(Clone, Hash)]
@@ -170,24 +266,256 @@ impl MissionStatus {
         }
     }
 }
+/// Keeps track of missions and which units are on them
 #[derive(Default)]
 pub struct ArmyController {
-    manager: AssignmentManager<crate::strong_types::Zealot, Mission, u64, usize>,
+    missions: HashMap<usize, Mission>,
+    units: HashMap<u64, UnitState>,
+    /// unit tag to mission id
+    assignments: HashMap<u64, usize>,
+    next_mission: usize,
 }
 impl ArmyController {
-    fn update_army_states(&self) {
-        todo!()
+    fn update_unit_state(&mut self, unit: UnitState) {
+        self.units.insert(unit.id(), unit);
     }
-    fn update_unit_state(&mut self, _: UnitState) {
-        todo!()
+    pub fn remove_unit(&mut self, tag: u64) {
+        self.units.remove(&tag);
+        self.assignments.remove(&tag);
     }
     fn command_all_units(&self) -> Vec<Result<Command, ArmyIssue>> {
-        todo!()
+        self.assignments
+            .iter()
+            .map(|(tag, mission)| {
+                let unit = self.units.get(tag).ok_or(ArmyIssue)?;
+                let mission = self.missions.get(mission).ok_or(ArmyIssue)?;
+                Ok(mission.command(unit))
+            })
+            .collect()
+    }
+    /// puts the unit on the newest unfinished mission that wants it
+    fn assign_unit(&mut self, unit: UnitState) -> Result<(), ArmyIssue> {
+        let mission = self
+            .missions
+            .values()
+            .filter(|m| !matches!(m.status, MissionStatus::Complete) && m.needs(&unit))
+            .map(|m| m.id)
+            .max()
+            .ok_or(ArmyIssue)?;
+        self.assign_to(unit, mission)
+    }
+    /// puts the unit on this mission, taking it off any other
+    pub fn assign_to(&mut self, unit: UnitState, mission: usize) -> Result<(), ArmyIssue> {
+        let mission_state = self.missions.get_mut(&mission).ok_or(ArmyIssue)?;
+        mission_state.status = mission_state.status.clone().begin();
+        self.assignments.insert(unit.id(), mission);
+        self.units.insert(unit.id(), unit);
+        Ok(())
+    }
+    fn add_mission(&mut self, mission_type: MissionType, rally: Point2) -> usize {
+        let id = self.next_mission;
+        self.next_mission += 1;
+        self.missions
+            .insert(id, Mission::new(id, mission_type, rally));
+        id
+    }
+    /// an unfinished mission with this exact job
+    pub fn find_mission(&self, mission_type: &MissionType) -> Option<usize> {
+        self.missions
+            .values()
+            .filter(|m| {
+                &m.mission_type == mission_type && !matches!(m.status, MissionStatus::Complete)
+            })
+            .map(|m| m.id)
+            .max()
+    }
+    /// Moves every non-detector we know of onto the mission,
+    /// unless it's scouting, babysitting or otherwise busy.
+    pub fn gather_army(&mut self, mission: usize) {
+        let free: Vec<UnitState> = self
+            .units
+            .values()
+            .filter(|u| !u.is_detector)
+            .filter(|u| {
+                self.assignments
+                    .get(&u.tag)
+                    .and_then(|m| self.missions.get(m))
+                    .is_none_or(Mission::is_fight)
+            })
+            .cloned()
+            .collect();
+        for unit in free {
+            let _ = self.assign_to(unit, mission);
+        }
+    }
+    /// puts the unit on the newest unfinished fight
+    fn join_fight(&mut self, unit: UnitState) -> Result<(), ArmyIssue> {
+        let mission = self
+            .missions
+            .values()
+            .filter(|m| m.is_fight() && !matches!(m.status, MissionStatus::Complete))
+            .map(|m| m.id)
+            .max()
+            .ok_or(ArmyIssue)?;
+        self.assign_to(unit, mission)
+    }
+    /// Marks the mission done and forgets everyone who was on it,
+    /// fighting units come back as free ones the next time the army is looked at.
+    /// Returns the tags of the units that were on it.
+    pub fn finish_mission(&mut self, mission: usize) -> Vec<u64> {
+        if let Some(m) = self.missions.get_mut(&mission) {
+            m.status = m.status.clone().finish();
+        }
+        let freed: Vec<u64> = self
+            .assignments
+            .iter()
+            .filter(|(_, m)| **m == mission)
+            .map(|(tag, _)| *tag)
+            .collect();
+        for tag in &freed {
+            self.remove_unit(*tag);
+        }
+        freed
+    }
+    /// Scouting missions that should end: nobody wants that type scouting any more,
+    /// or `arrived` says the scout is at the mission's target.
+    fn finished_scouting(
+        &self,
+        wanted: &[UnitTypeId],
+        arrived: impl Fn(u64, Point2) -> bool,
+    ) -> Vec<usize> {
+        let mut finished: Vec<usize> = self
+            .assignments
+            .iter()
+            .filter_map(|(tag, mission)| {
+                let mission = self.missions.get(mission)?;
+                let MissionType::Scout(target) = mission.mission_type else {
+                    return None;
+                };
+                let unit = self.units.get(tag)?;
+                let done = !matches!(mission.status, MissionStatus::Complete)
+                    && (!wanted.contains(&unit.unit_type) || arrived(*tag, target));
+                done.then_some(mission.id)
+            })
+            .collect();
+        finished.sort_unstable();
+        finished.dedup();
+        finished
+    }
+    /// where the newest unfinished fight is gathering
+    pub fn rally_point(&self) -> Option<Point2> {
+        self.missions
+            .values()
+            .filter(|m| m.is_fight() && !matches!(m.status, MissionStatus::Complete))
+            .max_by_key(|m| m.id)
+            .map(|m| m.rally_point)
+    }
+    /// whether a unit of this type is on a scouting mission right now
+    pub fn is_scouting(&self, unit_type: UnitTypeId) -> bool {
+        self.assignments.iter().any(|(tag, mission)| {
+            self.missions
+                .get(mission)
+                .is_some_and(|m| matches!(m.mission_type, MissionType::Scout(_)))
+                && self
+                    .units
+                    .get(tag)
+                    .is_some_and(|u| u.unit_type == unit_type)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(tag: u64, unit_type: UnitTypeId) -> UnitState {
+        UnitState {
+            is_detector: unit_type == UnitTypeId::Observer,
+            tag,
+            unit_type,
+        }
     }
-    fn assign_unit(&self, _: UnitState) -> Result<(), ArmyIssue> {
-        todo!()
+
+    #[test]
+    fn attacks_gather_the_free_army() {
+        let mut army = ArmyController::default();
+        let home = Point2::new(10.0, 10.0);
+        let there = Point2::new(90.0, 90.0);
+        for state in [
+            unit(1, UnitTypeId::Zealot),
+            unit(2, UnitTypeId::Stalker),
+            unit(3, UnitTypeId::Observer),
+            unit(4, UnitTypeId::Probe),
+        ] {
+            army.update_unit_state(state);
+        }
+        let scouting = army.add_mission(MissionType::Scout(there), home);
+        assert!(army.assign_to(unit(4, UnitTypeId::Probe), scouting).is_ok());
+        assert!(army.is_scouting(UnitTypeId::Probe));
+
+        let defend = army.add_mission(MissionType::DefendBase(home), home);
+        army.gather_army(defend);
+        assert_eq!(army.command_all_units().len(), 3);
+
+        // attacking pulls the defenders but leaves the scout and the observer alone
+        let attack = army.add_mission(MissionType::AttackEnemy(there), home);
+        assert_eq!(
+            army.find_mission(&MissionType::AttackEnemy(there)),
+            Some(attack)
+        );
+        army.gather_army(attack);
+        assert_eq!(army.assignments.get(&1), Some(&attack));
+        assert_eq!(army.assignments.get(&2), Some(&attack));
+        assert_eq!(army.assignments.get(&3), None);
+        assert_eq!(army.assignments.get(&4), Some(&scouting));
+
+        army.remove_unit(4);
+        assert!(!army.is_scouting(UnitTypeId::Probe));
     }
-    fn add_mission(&mut self, _: MissionType, _: Point2) -> usize {
-        todo!()
+
+    #[test]
+    fn finished_scouts_are_let_go() {
+        let mut army = ArmyController::default();
+        let home = Point2::new(10.0, 10.0);
+        let there = Point2::new(90.0, 90.0);
+        let scouting = army.add_mission(MissionType::Scout(there), home);
+        assert!(army.assign_to(unit(4, UnitTypeId::Probe), scouting).is_ok());
+
+        // still wanted and still on the way
+        let on_the_way = |_, _| false;
+        assert!(army
+            .finished_scouting(&[UnitTypeId::Probe], on_the_way)
+            .is_empty());
+        // the build stopped asking, or the scout got there
+        assert_eq!(army.finished_scouting(&[], on_the_way), [scouting]);
+        assert_eq!(
+            army.finished_scouting(&[UnitTypeId::Probe], |tag, target| tag == 4
+                && target == there),
+            [scouting]
+        );
+
+        assert_eq!(army.finish_mission(scouting), [4]);
+        assert!(!army.is_scouting(UnitTypeId::Probe));
+        assert!(army.assignments.is_empty());
+        assert!(army.finished_scouting(&[], on_the_way).is_empty());
+
+        // the probe doesn't get dragged into the next fight
+        let attack = army.add_mission(MissionType::AttackEnemy(there), home);
+        army.gather_army(attack);
+        assert!(army.command_all_units().is_empty());
+    }
+
+    #[test]
+    fn new_units_join_the_fight() {
+        let mut army = ArmyController::default();
+        let home = Point2::new(10.0, 10.0);
+        let there = Point2::new(90.0, 90.0);
+        assert!(army.join_fight(unit(1, UnitTypeId::Zealot)).is_err());
+
+        let scouting = army.add_mission(MissionType::Scout(there), home);
+        let attack = army.add_mission(MissionType::AttackEnemy(there), home);
+        assert!(army.join_fight(unit(1, UnitTypeId::Zealot)).is_ok());
+        assert_eq!(army.assignments.get(&1), Some(&attack));
+        assert_ne!(army.assignments.get(&1), Some(&scouting));
     }
 }
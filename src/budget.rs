@@ -0,0 +1,139 @@
+use std::cmp::Reverse;
+
+use crate::{build_orders::BuildOrderAction, build_tree::BuildComponent};
+
+/// Minerals and gas, either what we have to spend or what something costs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub minerals: u32,
+    pub gas: u32,
+}
+
+impl Budget {
+    pub const fn new(minerals: u32, gas: u32) -> Self {
+        Self { minerals, gas }
+    }
+
+    pub const fn covers(&self, cost: Self) -> bool {
+        self.minerals >= cost.minerals && self.gas >= cost.gas
+    }
+
+    /// takes the cost out, or as much of it as there is
+    pub const fn spend(&mut self, cost: Self) {
+        self.minerals = self.minerals.saturating_sub(cost.minerals);
+        self.gas = self.gas.saturating_sub(cost.gas);
+    }
+
+    pub const fn add(&mut self, cost: Self) {
+        self.minerals += cost.minerals;
+        self.gas += cost.gas;
+    }
+}
+
+/// Picks which actions of the active components get paid for this step.
+/// Higher priorities go first, ties go in tree order. A component that can't afford its
+/// action yet holds that money back from every lower priority, so a probe `Train`
+/// can't keep starving an `Expand` that matters more.
+/// Components at the same priority don't hold money for each other.
+pub fn fund_actions<C, D>(
+    components: &[BuildComponent],
+    mut budget: Budget,
+    cost_of: C,
+    can_do: D,
+) -> Vec<BuildOrderAction>
+where
+    C: Fn(BuildOrderAction) -> Budget,
+    D: Fn(BuildOrderAction) -> bool,
+{
+    let mut by_priority: Vec<&BuildComponent> = components.iter().collect();
+    by_priority.sort_by_key(|c| Reverse(c.priority()));
+
+    let mut funded = Vec::new();
+    let mut reserved = Budget::default();
+    let mut current_priority = None;
+    for component in by_priority {
+        if current_priority != Some(component.priority()) {
+            budget.spend(reserved);
+            reserved = Budget::default();
+            current_priority = Some(component.priority());
+        }
+        let Some(action) = component.action() else {
+            continue;
+        };
+        if !can_do(action) {
+            continue;
+        }
+        let cost = cost_of(action);
+        if budget.covers(cost) {
+            budget.spend(cost);
+            funded.push(action);
+        } else {
+            reserved.add(cost);
+        }
+    }
+    funded
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_sc2::ids::{AbilityId, UnitTypeId};
+
+    use super::*;
+    use crate::build_orders::Priority;
+
+    const PROBE: BuildOrderAction =
+        BuildOrderAction::Train(UnitTypeId::Probe, AbilityId::NexusTrainProbe);
+
+    fn cost_of(action: BuildOrderAction) -> Budget {
+        match action {
+            BuildOrderAction::Expand => Budget::new(400, 0),
+            BuildOrderAction::Train(_, _) => Budget::new(50, 0),
+            _ => Budget::default(),
+        }
+    }
+
+    #[test]
+    fn saving_up_for_a_nexus() {
+        let components = [
+            BuildComponent::doing(PROBE, Priority::Normal),
+            BuildComponent::doing(BuildOrderAction::Expand, Priority::High),
+            BuildComponent::doing(
+                BuildOrderAction::Chrono(AbilityId::NexusTrainProbe),
+                Priority::Low,
+            ),
+        ];
+
+        // the probe waits while the nexus is saved for, but free things still happen
+        assert_eq!(
+            fund_actions(&components, Budget::new(300, 0), cost_of, |_| true),
+            vec![BuildOrderAction::Chrono(AbilityId::NexusTrainProbe)]
+        );
+        // enough for both
+        assert_eq!(
+            fund_actions(&components, Budget::new(450, 0), cost_of, |_| true),
+            vec![
+                BuildOrderAction::Expand,
+                PROBE,
+                BuildOrderAction::Chrono(AbilityId::NexusTrainProbe)
+            ]
+        );
+        // a nexus we can't build doesn't hold anything back
+        assert_eq!(
+            fund_actions(&components, Budget::new(300, 0), cost_of, |a| a
+                != BuildOrderAction::Expand),
+            vec![PROBE, BuildOrderAction::Chrono(AbilityId::NexusTrainProbe)]
+        );
+    }
+
+    #[test]
+    fn same_priority_shares() {
+        let components = [
+            BuildComponent::doing(BuildOrderAction::Expand, Priority::Normal),
+            BuildComponent::doing(PROBE, Priority::Normal),
+        ];
+        assert_eq!(
+            fund_actions(&components, Budget::new(300, 0), cost_of, |_| true),
+            vec![PROBE]
+        );
+    }
+}
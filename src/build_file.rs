@@ -0,0 +1,954 @@
+use std::{fmt::Display, fs, path::Path};
+
+use rust_sc2::{
+    ids::{AbilityId, UnitTypeId, UpgradeId},
+    player::Race,
+};
+
+use crate::{
+    build_orders::{BuildCondition, BuildOrderAction, MapTarget, Priority},
+    build_tree::{
+        BuildComponent, BuildOrderTree, ConditionGroup, ConditionOperator, ConditionTerm, TreeError,
+    },
+    chatter::ChatAction,
+    production::Quota,
+    upgrades::UpgradeLine,
+};
+
+/// Where checked-in build orders live, relative to the working directory.
+pub const BUILDS_DIRECTORY: &str = "builds";
+pub const BUILD_FILE_EXTENSION: &str = "build";
+
+/// A build file is one node per line. Leading dashes give the depth,
+/// the same way the tree displays itself. Fields are separated by `|`:
+///
+/// ```text
+/// # comments start with a hash
+/// probe to 14 | do: Train(Probe, NexusTrainProbe) | done when: NoneOf(SupplyBetween(0, 15))
+/// -first pylon | do: Construct(Pylon) | done when: All(AtLeastCount(Pylon, 1))
+/// -gate | start when: All(SupplyAtLeast(16)) | do: Construct(Gateway) | done when: All(AtLeastCount(Gateway, 1))
+/// units | hidden | top level
+/// -keep 2 observers | do: Train(Observer, RoboticsFacilityTrainObserver) | done when: All(TotalAndOrderedAtLeast(Observer, 2)) | repeat
+/// ```
+///
+/// Nodes at depth zero hang off the tree's ROOT, unless they're marked `top level`,
+/// then they sit next to ROOT and run alongside it.
+/// `repeat` nodes go back to work whenever their done conditions stop holding.
+/// `reaction` nodes take over from their siblings' branches as soon as they start.
+/// Actions and conditions are written like their `Debug` output.
+impl BuildOrderTree {
+    /// Loads a build from `builds/<name>.build`
+    /// # Errors
+    /// Any `BuildFileError` from reading or parsing the file.
+    pub fn from_name(name: &str) -> Result<Self, BuildFileError> {
+        let path = Path::new(BUILDS_DIRECTORY)
+            .join(name)
+            .with_extension(BUILD_FILE_EXTENSION);
+        Self::from_file(path)
+    }
+
+    /// Names of every build in `builds/`, sorted so they come out the same every time.
+    /// A missing directory just means no builds.
+    pub fn available() -> Vec<String> {
+        let Ok(entries) = fs::read_dir(BUILDS_DIRECTORY) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == BUILD_FILE_EXTENSION))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Reads and parses a build order file.
+    /// # Errors
+    /// `BuildFileError::Io` if the file can't be read,
+    /// otherwise the first problem found while parsing, with its line number.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, BuildFileError> {
+        let text = fs::read_to_string(path).map_err(BuildFileError::Io)?;
+        Self::parse(&text)
+    }
+
+    /// Parses the text of a build order file.
+    /// # Errors
+    /// The first problem found, with its line number.
+    pub fn parse(text: &str) -> Result<Self, BuildFileError> {
+        let mut tree = Self::default();
+        // the most recent node at each depth, so children can find their parent
+        let mut lineage: Vec<usize> = Vec::new();
+
+        for (line_index, raw_line) in text.lines().enumerate() {
+            let line = line_index + 1;
+            let trimmed = raw_line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let depth = trimmed.chars().take_while(|c| *c == '-').count();
+            if depth > lineage.len() {
+                return Err(BuildFileError::BadNesting {
+                    line,
+                    depth,
+                    deepest: lineage.len(),
+                });
+            }
+            lineage.truncate(depth);
+            let (component, top_level) = parse_component(&trimmed[depth..])
+                .map_err(|problem| BuildFileError::Syntax { line, problem })?;
+            let parent = match lineage.last() {
+                Some(_) if top_level => {
+                    return Err(BuildFileError::Syntax {
+                        line,
+                        problem: ParseProblem::NestedTopLevel,
+                    })
+                }
+                Some(parent) => Some(*parent),
+                None if top_level => None,
+                None => Some(0),
+            };
+            let index = tree
+                .add_node(component, parent)
+                .map_err(|error| BuildFileError::Tree { line, error })?;
+            lineage.push(index);
+        }
+        Ok(tree)
+    }
+}
+
+/// the component on a line, and whether it's marked `top level`
+fn parse_component(text: &str) -> Result<(BuildComponent, bool), ParseProblem> {
+    let mut fields = text.split('|').map(str::trim);
+    let name = fields.next().unwrap_or_default();
+    if name.is_empty() {
+        return Err(ParseProblem::MissingName);
+    }
+
+    let mut action = None;
+    let mut start_when = ConditionGroup::new(&[], ConditionOperator::All);
+    let mut complete_when = ConditionGroup::new(&[], ConditionOperator::All);
+    let mut display = true;
+    let mut priority = Priority::default();
+    let mut repeats = false;
+    let mut reaction = false;
+    let mut top_level = false;
+
+    for field in fields {
+        if field == "hidden" {
+            display = false;
+            continue;
+        }
+        if field == "repeat" {
+            repeats = true;
+            continue;
+        }
+        if field == "reaction" {
+            reaction = true;
+            continue;
+        }
+        if field == "top level" {
+            top_level = true;
+            continue;
+        }
+        let (key, value) = field
+            .split_once(':')
+            .ok_or_else(|| ParseProblem::MalformedField(field.to_string()))?;
+        let term = Term::parse(value.trim())?;
+        match key.trim() {
+            "do" => action = term.action()?,
+            "start when" => start_when = term.condition_group()?,
+            "done when" => complete_when = term.condition_group()?,
+            "priority" => priority = term.priority()?,
+            other => return Err(ParseProblem::UnknownField(other.to_string())),
+        }
+    }
+
+    let mut component = BuildComponent::new(name, start_when, complete_when, action, display)
+        .with_priority(priority);
+    if repeats {
+        component = component.repeating();
+    }
+    if reaction {
+        component = component.reacting();
+    }
+    Ok((component, top_level))
+}
+
+/// A `Name(arg, arg)` expression from a build file.
+#[derive(Debug, PartialEq, Eq)]
+struct Term {
+    name: String,
+    args: Vec<Term>,
+}
+
+impl Term {
+    fn parse(text: &str) -> Result<Self, ParseProblem> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut position = 0;
+        let term = Self::parse_at(&chars, &mut position)?;
+        skip_whitespace(&chars, &mut position);
+        if position < chars.len() {
+            return Err(ParseProblem::TrailingText(
+                chars[position..].iter().collect(),
+            ));
+        }
+        Ok(term)
+    }
+
+    fn parse_at(chars: &[char], position: &mut usize) -> Result<Self, ParseProblem> {
+        skip_whitespace(chars, position);
+        let start = *position;
+        while chars
+            .get(*position)
+            .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+        {
+            *position += 1;
+        }
+        if start == *position {
+            return Err(ParseProblem::ExpectedName(*position));
+        }
+        let name: String = chars[start..*position].iter().collect();
+
+        let mut args = Vec::new();
+        skip_whitespace(chars, position);
+        if chars.get(*position) == Some(&'(') {
+            *position += 1;
+            skip_whitespace(chars, position);
+            if chars.get(*position) == Some(&')') {
+                *position += 1;
+            } else {
+                loop {
+                    args.push(Self::parse_at(chars, position)?);
+                    skip_whitespace(chars, position);
+                    match chars.get(*position) {
+                        Some(',') => *position += 1,
+                        Some(')') => {
+                            *position += 1;
+                            break;
+                        }
+                        _ => return Err(ParseProblem::UnclosedParenthesis(name)),
+                    }
+                }
+            }
+        }
+        Ok(Self { name, args })
+    }
+
+    fn expect_args(&self, count: usize) -> Result<&[Self], ParseProblem> {
+        if self.args.len() == count {
+            Ok(&self.args)
+        } else {
+            Err(ParseProblem::WrongArgumentCount {
+                name: self.name.clone(),
+                expected: count,
+                found: self.args.len(),
+            })
+        }
+    }
+
+    fn bare(&self) -> Result<&str, ParseProblem> {
+        self.expect_args(0)?;
+        Ok(&self.name)
+    }
+
+    fn number<T: std::str::FromStr>(&self) -> Result<T, ParseProblem> {
+        self.bare()?
+            .parse()
+            .map_err(|_| ParseProblem::ExpectedNumber(self.name.clone()))
+    }
+
+    fn unit(&self) -> Result<UnitTypeId, ParseProblem> {
+        lookup(self.bare()?, &KNOWN_UNITS, "unit type")
+    }
+
+    fn upgrade(&self) -> Result<UpgradeId, ParseProblem> {
+        lookup(self.bare()?, &KNOWN_UPGRADES, "upgrade")
+    }
+
+    fn upgrade_line(&self) -> Result<UpgradeLine, ParseProblem> {
+        lookup(self.bare()?, &UpgradeLine::ALL, "upgrade line")
+    }
+
+    fn ability(&self) -> Result<AbilityId, ParseProblem> {
+        lookup(self.bare()?, &KNOWN_ABILITIES, "ability")
+    }
+
+    fn priority(&self) -> Result<Priority, ParseProblem> {
+        lookup(
+            self.bare()?,
+            &[
+                Priority::Low,
+                Priority::Normal,
+                Priority::High,
+                Priority::Urgent,
+            ],
+            "priority",
+        )
+    }
+
+    fn race(&self) -> Result<Race, ParseProblem> {
+        lookup(
+            self.bare()?,
+            &[Race::Protoss, Race::Terran, Race::Zerg],
+            "race",
+        )
+    }
+
+    fn map_target(&self) -> Result<MapTarget, ParseProblem> {
+        lookup(
+            self.bare()?,
+            &[
+                MapTarget::OurMain,
+                MapTarget::OurNatural,
+                MapTarget::EnemyMain,
+                MapTarget::EnemyNatural,
+                MapTarget::MapCenter,
+            ],
+            "map target",
+        )
+    }
+
+    fn action(&self) -> Result<Option<BuildOrderAction>, ParseProblem> {
+        use BuildOrderAction as A;
+        let action = match self.name.as_str() {
+            "None" => {
+                self.expect_args(0)?;
+                return Ok(None);
+            }
+            "Train" => {
+                let args = self.expect_args(2)?;
+                A::Train(args[0].unit()?, args[1].ability()?)
+            }
+            "Construct" => A::Construct(self.expect_args(1)?[0].unit()?),
+            "Chrono" => A::Chrono(self.expect_args(1)?[0].ability()?),
+            "ChronoWhatever" => A::ChronoWhatever(self.expect_args(1)?[0].unit()?),
+            "Research" => {
+                let args = self.expect_args(3)?;
+                A::Research(args[0].upgrade()?, args[1].ability()?, args[2].unit()?)
+            }
+            "Produce" => {
+                let args = self.expect_args(2)?;
+                A::Produce(args[0].unit()?, args[1].quota()?)
+            }
+            "UpgradeTo" => {
+                let args = self.expect_args(2)?;
+                A::UpgradeTo(args[0].upgrade_line()?, args[1].number()?)
+            }
+            "Expand" => {
+                self.expect_args(0)?;
+                A::Expand
+            }
+            "Chat" => A::Chat(self.expect_args(1)?[0].chat()?),
+            "Surrender" => {
+                self.expect_args(0)?;
+                A::Surrender
+            }
+            "Attack" => A::Attack(self.expect_args(1)?[0].map_target()?),
+            "Defend" => A::Defend(self.expect_args(1)?[0].map_target()?),
+            "SendScout" => A::SendScout(self.expect_args(1)?[0].unit()?),
+            "CancelStructure" => A::CancelStructure(self.expect_args(1)?[0].unit()?),
+            "SetRally" => {
+                let args = self.expect_args(2)?;
+                A::SetRally(args[0].unit()?, args[1].map_target()?)
+            }
+            other => return Err(ParseProblem::UnknownName("action", other.to_string())),
+        };
+        Ok(Some(action))
+    }
+
+    fn chat(&self) -> Result<ChatAction, ParseProblem> {
+        match self.name.as_str() {
+            "Greeting" => self.expect_args(0).map(|_| ChatAction::Greeting),
+            "AdmitDefeat" => self.expect_args(0).map(|_| ChatAction::AdmitDefeat),
+            "Anticipate" => Ok(ChatAction::Anticipate(self.expect_args(1)?[0].number()?)),
+            "Taunt" => Ok(ChatAction::Taunt(self.expect_args(1)?[0].unit()?)),
+            other => Err(ParseProblem::UnknownName("chat", other.to_string())),
+        }
+    }
+
+    fn quota(&self) -> Result<Quota, ParseProblem> {
+        match self.name.as_str() {
+            "Percent" => Ok(Quota::Percent(self.expect_args(1)?[0].number()?)),
+            "Count" => Ok(Quota::Count(self.expect_args(1)?[0].number()?)),
+            other => Err(ParseProblem::UnknownName("quota", other.to_string())),
+        }
+    }
+
+    fn condition(&self) -> Result<BuildCondition, ParseProblem> {
+        use BuildCondition as C;
+        let condition = match self.name.as_str() {
+            "SupplyAtLeast" => C::SupplyAtLeast(self.expect_args(1)?[0].number()?),
+            "SupplyBetween" => {
+                let args = self.expect_args(2)?;
+                C::SupplyBetween(args[0].number()?, args[1].number()?)
+            }
+            "SupplyLeftBelow" => C::SupplyLeftBelow(self.expect_args(1)?[0].number()?),
+            "TechComplete" => C::TechComplete(self.expect_args(1)?[0].upgrade()?),
+            "StructureComplete" => C::StructureComplete(self.expect_args(1)?[0].unit()?),
+            "LessThanCount" => {
+                let args = self.expect_args(2)?;
+                C::LessThanCount(args[0].unit()?, args[1].number()?)
+            }
+            "AtLeastCount" => {
+                let args = self.expect_args(2)?;
+                C::AtLeastCount(args[0].unit()?, args[1].number()?)
+            }
+            "DontHaveAnyDone" => C::DontHaveAnyDone(self.expect_args(1)?[0].unit()?),
+            "DontHaveAnyStarted" => C::DontHaveAnyStarted(self.expect_args(1)?[0].unit()?),
+            "Always" => self.expect_args(0).map(|_| C::Always)?,
+            "Never" => self.expect_args(0).map(|_| C::Never)?,
+            "TotalAndOrderedAtLeast" => {
+                let args = self.expect_args(2)?;
+                C::TotalAndOrderedAtLeast(args[0].unit()?, args[1].number()?)
+            }
+            "EnemyRaceIs" => C::EnemyRaceIs(self.expect_args(1)?[0].race()?),
+            "EnemyUnitSeen" => C::EnemyUnitSeen(self.expect_args(1)?[0].unit()?),
+            "EnemyUnitSeenBefore" => {
+                let args = self.expect_args(2)?;
+                C::EnemyUnitSeenBefore(args[0].unit()?, args[1].number()?)
+            }
+            "EnemyStructureCountAtLeast" => {
+                let args = self.expect_args(2)?;
+                C::EnemyStructureCountAtLeast(args[0].unit()?, args[1].number()?)
+            }
+            "EnemyExpansionsAtLeast" => {
+                C::EnemyExpansionsAtLeast(self.expect_args(1)?[0].number()?)
+            }
+            "GameTimeAtLeast" => C::GameTimeAtLeast(self.expect_args(1)?[0].number()?),
+            "MineralsAtLeast" => C::MineralsAtLeast(self.expect_args(1)?[0].number()?),
+            "MineralsBelow" => C::MineralsBelow(self.expect_args(1)?[0].number()?),
+            "GasAtLeast" => C::GasAtLeast(self.expect_args(1)?[0].number()?),
+            "GasBelow" => C::GasBelow(self.expect_args(1)?[0].number()?),
+            "BasesSaturatedAtLeast" => C::BasesSaturatedAtLeast(self.expect_args(1)?[0].number()?),
+            "IdleProductionAtLeast" => {
+                let args = self.expect_args(2)?;
+                C::IdleProductionAtLeast(args[0].unit()?, args[1].number()?)
+            }
+            other => return Err(ParseProblem::UnknownName("condition", other.to_string())),
+        };
+        Ok(condition)
+    }
+
+    fn condition_group(&self) -> Result<ConditionGroup, ParseProblem> {
+        let (operator, conditions) = match self.name.as_str() {
+            "All" => (ConditionOperator::All, &self.args[..]),
+            "NotAll" => (ConditionOperator::NotAll, &self.args[..]),
+            "Any" => (ConditionOperator::Any, &self.args[..]),
+            "NoneOf" => (ConditionOperator::NoneOf, &self.args[..]),
+            "ExactlyNOf" => {
+                let (n, rest) = self
+                    .args
+                    .split_first()
+                    .ok_or(ParseProblem::ExpectedNumber(self.name.clone()))?;
+                (ConditionOperator::ExactlyNOf(n.number()?), rest)
+            }
+            other => return Err(ParseProblem::UnknownName("operator", other.to_string())),
+        };
+        let terms = conditions
+            .iter()
+            .map(Self::condition_term)
+            .collect::<Result<Vec<ConditionTerm>, ParseProblem>>()?;
+        Ok(ConditionGroup::nested(terms, operator))
+    }
+
+    /// an operator name starts a nested group, anything else is a condition
+    fn condition_term(&self) -> Result<ConditionTerm, ParseProblem> {
+        if OPERATORS.contains(&self.name.as_str()) {
+            self.condition_group().map(ConditionTerm::Group)
+        } else {
+            self.condition().map(ConditionTerm::Condition)
+        }
+    }
+}
+
+const OPERATORS: [&str; 5] = ["All", "NotAll", "Any", "NoneOf", "ExactlyNOf"];
+
+fn skip_whitespace(chars: &[char], position: &mut usize) {
+    while chars.get(*position).is_some_and(|c| c.is_whitespace()) {
+        *position += 1;
+    }
+}
+
+/// the ids don't parse from strings, so match on their debug names instead
+fn lookup<T: Copy + std::fmt::Debug>(
+    name: &str,
+    known: &[T],
+    kind: &'static str,
+) -> Result<T, ParseProblem> {
+    known
+        .iter()
+        .find(|id| format!("{id:?}") == name)
+        .copied()
+        .ok_or_else(|| ParseProblem::UnknownName(kind, name.to_string()))
+}
+
+/// ours first, then the ones we might see from the enemy
+const KNOWN_UNITS: [UnitTypeId; 97] = [
+    UnitTypeId::Probe,
+    UnitTypeId::Zealot,
+    UnitTypeId::Stalker,
+    UnitTypeId::Sentry,
+    UnitTypeId::Adept,
+    UnitTypeId::HighTemplar,
+    UnitTypeId::DarkTemplar,
+    UnitTypeId::Archon,
+    UnitTypeId::Immortal,
+    UnitTypeId::Colossus,
+    UnitTypeId::Disruptor,
+    UnitTypeId::Observer,
+    UnitTypeId::WarpPrism,
+    UnitTypeId::Phoenix,
+    UnitTypeId::VoidRay,
+    UnitTypeId::Oracle,
+    UnitTypeId::Tempest,
+    UnitTypeId::Carrier,
+    UnitTypeId::Mothership,
+    UnitTypeId::Nexus,
+    UnitTypeId::Pylon,
+    UnitTypeId::Assimilator,
+    UnitTypeId::Gateway,
+    UnitTypeId::WarpGate,
+    UnitTypeId::Forge,
+    UnitTypeId::CyberneticsCore,
+    UnitTypeId::PhotonCannon,
+    UnitTypeId::ShieldBattery,
+    UnitTypeId::TwilightCouncil,
+    UnitTypeId::RoboticsFacility,
+    UnitTypeId::RoboticsBay,
+    UnitTypeId::Stargate,
+    UnitTypeId::FleetBeacon,
+    UnitTypeId::TemplarArchive,
+    UnitTypeId::DarkShrine,
+    UnitTypeId::SCV,
+    UnitTypeId::Marine,
+    UnitTypeId::Marauder,
+    UnitTypeId::Reaper,
+    UnitTypeId::Ghost,
+    UnitTypeId::Hellion,
+    UnitTypeId::WidowMine,
+    UnitTypeId::SiegeTank,
+    UnitTypeId::Cyclone,
+    UnitTypeId::Thor,
+    UnitTypeId::VikingFighter,
+    UnitTypeId::Medivac,
+    UnitTypeId::Liberator,
+    UnitTypeId::Raven,
+    UnitTypeId::Banshee,
+    UnitTypeId::Battlecruiser,
+    UnitTypeId::CommandCenter,
+    UnitTypeId::OrbitalCommand,
+    UnitTypeId::PlanetaryFortress,
+    UnitTypeId::SupplyDepot,
+    UnitTypeId::Refinery,
+    UnitTypeId::Barracks,
+    UnitTypeId::Factory,
+    UnitTypeId::Starport,
+    UnitTypeId::EngineeringBay,
+    UnitTypeId::Bunker,
+    UnitTypeId::MissileTurret,
+    UnitTypeId::Armory,
+    UnitTypeId::FusionCore,
+    UnitTypeId::GhostAcademy,
+    UnitTypeId::Drone,
+    UnitTypeId::Overlord,
+    UnitTypeId::Queen,
+    UnitTypeId::Zergling,
+    UnitTypeId::Baneling,
+    UnitTypeId::Roach,
+    UnitTypeId::Ravager,
+    UnitTypeId::Hydralisk,
+    UnitTypeId::LurkerMP,
+    UnitTypeId::Infestor,
+    UnitTypeId::SwarmHostMP,
+    UnitTypeId::Ultralisk,
+    UnitTypeId::Mutalisk,
+    UnitTypeId::Corruptor,
+    UnitTypeId::BroodLord,
+    UnitTypeId::Viper,
+    UnitTypeId::Hatchery,
+    UnitTypeId::Lair,
+    UnitTypeId::Hive,
+    UnitTypeId::SpawningPool,
+    UnitTypeId::Extractor,
+    UnitTypeId::EvolutionChamber,
+    UnitTypeId::RoachWarren,
+    UnitTypeId::BanelingNest,
+    UnitTypeId::HydraliskDen,
+    UnitTypeId::LurkerDenMP,
+    UnitTypeId::Spire,
+    UnitTypeId::InfestationPit,
+    UnitTypeId::UltraliskCavern,
+    UnitTypeId::SpineCrawler,
+    UnitTypeId::SporeCrawler,
+    UnitTypeId::NydusNetwork,
+];
+
+const KNOWN_UPGRADES: [UpgradeId; 27] = [
+    UpgradeId::ProtossGroundWeaponsLevel1,
+    UpgradeId::ProtossGroundWeaponsLevel2,
+    UpgradeId::ProtossGroundWeaponsLevel3,
+    UpgradeId::ProtossGroundArmorsLevel1,
+    UpgradeId::ProtossGroundArmorsLevel2,
+    UpgradeId::ProtossGroundArmorsLevel3,
+    UpgradeId::ProtossAirWeaponsLevel1,
+    UpgradeId::ProtossAirWeaponsLevel2,
+    UpgradeId::ProtossAirWeaponsLevel3,
+    UpgradeId::ProtossAirArmorsLevel1,
+    UpgradeId::ProtossAirArmorsLevel2,
+    UpgradeId::ProtossAirArmorsLevel3,
+    UpgradeId::ProtossShieldsLevel1,
+    UpgradeId::ProtossShieldsLevel2,
+    UpgradeId::ProtossShieldsLevel3,
+    UpgradeId::WarpGateResearch,
+    UpgradeId::PsiStormTech,
+    UpgradeId::DarkTemplarBlinkUpgrade,
+    UpgradeId::Charge,
+    UpgradeId::BlinkTech,
+    UpgradeId::AdeptPiercingAttack,
+    UpgradeId::GraviticDrive,
+    UpgradeId::ObserverGraviticBooster,
+    UpgradeId::ExtendedThermalLance,
+    UpgradeId::AnionPulseCrystals,
+    UpgradeId::VoidRaySpeedUpgrade,
+    UpgradeId::TempestGroundAttackUpgrade,
+];
+
+const KNOWN_ABILITIES: [AbilityId; 47] = [
+    AbilityId::NexusTrainProbe,
+    AbilityId::GatewayTrainZealot,
+    AbilityId::GatewayTrainStalker,
+    AbilityId::GatewayTrainSentry,
+    AbilityId::GatewayTrainHighTemplar,
+    AbilityId::GatewayTrainDarkTemplar,
+    AbilityId::TrainAdept,
+    AbilityId::WarpGateTrainZealot,
+    AbilityId::WarpGateTrainStalker,
+    AbilityId::WarpGateTrainSentry,
+    AbilityId::WarpGateTrainHighTemplar,
+    AbilityId::WarpGateTrainDarkTemplar,
+    AbilityId::TrainWarpAdept,
+    AbilityId::RoboticsFacilityTrainObserver,
+    AbilityId::RoboticsFacilityTrainWarpPrism,
+    AbilityId::RoboticsFacilityTrainImmortal,
+    AbilityId::RoboticsFacilityTrainColossus,
+    AbilityId::TrainDisruptor,
+    AbilityId::StargateTrainPhoenix,
+    AbilityId::StargateTrainOracle,
+    AbilityId::StargateTrainVoidRay,
+    AbilityId::StargateTrainTempest,
+    AbilityId::StargateTrainCarrier,
+    AbilityId::ResearchWarpGate,
+    AbilityId::ResearchCharge,
+    AbilityId::ResearchBlink,
+    AbilityId::ResearchAdeptResonatingGlaives,
+    AbilityId::ResearchPsiStorm,
+    AbilityId::ResearchShadowStrike,
+    AbilityId::ResearchExtendedThermalLance,
+    AbilityId::ResearchGraviticBooster,
+    AbilityId::ResearchGraviticDrive,
+    AbilityId::ForgeResearchProtossGroundWeaponsLevel1,
+    AbilityId::ForgeResearchProtossGroundWeaponsLevel2,
+    AbilityId::ForgeResearchProtossGroundWeaponsLevel3,
+    AbilityId::ForgeResearchProtossGroundArmorLevel1,
+    AbilityId::ForgeResearchProtossGroundArmorLevel2,
+    AbilityId::ForgeResearchProtossGroundArmorLevel3,
+    AbilityId::ForgeResearchProtossShieldsLevel1,
+    AbilityId::ForgeResearchProtossShieldsLevel2,
+    AbilityId::ForgeResearchProtossShieldsLevel3,
+    AbilityId::CyberneticsCoreResearchProtossAirWeaponsLevel1,
+    AbilityId::CyberneticsCoreResearchProtossAirWeaponsLevel2,
+    AbilityId::CyberneticsCoreResearchProtossAirWeaponsLevel3,
+    AbilityId::CyberneticsCoreResearchProtossAirArmorsLevel1,
+    AbilityId::CyberneticsCoreResearchProtossAirArmorsLevel2,
+    AbilityId::CyberneticsCoreResearchProtossAirArmorsLevel3,
+];
+
+#[derive(Debug)]
+pub enum BuildFileError {
+    Io(std::io::Error),
+    Syntax {
+        line: usize,
+        problem: ParseProblem,
+    },
+    BadNesting {
+        line: usize,
+        depth: usize,
+        deepest: usize,
+    },
+    Tree {
+        line: usize,
+        error: TreeError,
+    },
+}
+
+impl Display for BuildFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Can't read build file: {e}"),
+            Self::Syntax { line, problem } => write!(f, "line {line}: {problem}"),
+            Self::BadNesting {
+                line,
+                depth,
+                deepest,
+            } => write!(
+                f,
+                "line {line}: nested {depth} deep, but the line above only allows {deepest}"
+            ),
+            Self::Tree { line, error } => write!(f, "line {line}: {error:?}"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseProblem {
+    MissingName,
+    MalformedField(String),
+    UnknownField(String),
+    ExpectedName(usize),
+    ExpectedNumber(String),
+    UnclosedParenthesis(String),
+    TrailingText(String),
+    WrongArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    UnknownName(&'static str, String),
+    NestedTopLevel,
+}
+
+impl Display for ParseProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingName => write!(f, "node has no name"),
+            Self::MalformedField(field) => write!(f, "expected `key: value`, found `{field}`"),
+            Self::UnknownField(key) => write!(f, "unknown field `{key}`"),
+            Self::ExpectedName(column) => write!(f, "expected a name at column {column}"),
+            Self::ExpectedNumber(found) => write!(f, "expected a number, found `{found}`"),
+            Self::UnclosedParenthesis(name) => write!(f, "unclosed parenthesis after `{name}`"),
+            Self::TrailingText(rest) => write!(f, "unexpected `{rest}`"),
+            Self::WrongArgumentCount {
+                name,
+                expected,
+                found,
+            } => write!(f, "`{name}` takes {expected} arguments, found {found}"),
+            Self::UnknownName(kind, name) => write!(f, "unknown {kind} `{name}`"),
+            Self::NestedTopLevel => write!(f, "only nodes at depth zero can be top level"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terms_nest() {
+        let term = Term::parse("Any(AtLeastCount(Gateway, 2), Always)");
+        assert_eq!(
+            term,
+            Ok(Term {
+                name: "Any".to_string(),
+                args: vec![
+                    Term {
+                        name: "AtLeastCount".to_string(),
+                        args: vec![
+                            Term {
+                                name: "Gateway".to_string(),
+                                args: vec![]
+                            },
+                            Term {
+                                name: "2".to_string(),
+                                args: vec![]
+                            }
+                        ]
+                    },
+                    Term {
+                        name: "Always".to_string(),
+                        args: vec![]
+                    }
+                ]
+            })
+        );
+        assert_eq!(
+            Term::parse("All(Always"),
+            Err(ParseProblem::UnclosedParenthesis("All".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_small_build() {
+        let text = "\
+# a comment
+probe to 14 | do: Train(Probe, NexusTrainProbe) | done when: NoneOf(SupplyBetween(0, 15))
+-first pylon | do: Construct(Pylon) | done when: All(AtLeastCount(Pylon, 1))
+--gate | start when: Any(Always) | do: Construct(Gateway) | done when: ExactlyNOf(1, Always, Never)
+-hidden checkpoint | hidden
+
+units | do: None
+-observers | repeat";
+        let tree = BuildOrderTree::parse(text);
+        assert!(tree.is_ok(), "{:?}", tree.err());
+        assert_eq!(
+            tree.unwrap().to_string(),
+            "ROOT➖\n-units➖\n--observers🔁➖\n-probe to 14➖\n--hidden checkpoint➖\n--first pylon➖\n---gate➖\n"
+        );
+    }
+
+    #[test]
+    fn nested_groups_round_trip() {
+        let text = "Any(All(StructureComplete(Gateway), AtLeastCount(Pylon, 2)), ExactlyNOf(1, Always, NoneOf(Never)))";
+        let group = Term::parse(text).and_then(|term| term.condition_group());
+        assert_eq!(group.map(|g| g.to_string()), Ok(text.to_string()));
+    }
+
+    #[test]
+    fn enemy_conditions_parse() {
+        let group = Term::parse(
+            "Any(EnemyRaceIs(Zerg), EnemyUnitSeenBefore(Zergling, 2000), EnemyExpansionsAtLeast(2))",
+        )
+        .and_then(|term| term.condition_group());
+        assert_eq!(
+            group,
+            Ok(ConditionGroup::new(
+                &[
+                    BuildCondition::EnemyRaceIs(Race::Zerg),
+                    BuildCondition::EnemyUnitSeenBefore(UnitTypeId::Zergling, 2000),
+                    BuildCondition::EnemyExpansionsAtLeast(2),
+                ],
+                ConditionOperator::Any
+            ))
+        );
+        assert_eq!(
+            Term::parse("EnemyRaceIs(Random)").and_then(|term| term.condition()),
+            Err(ParseProblem::UnknownName("race", "Random".to_string()))
+        );
+    }
+
+    #[test]
+    fn army_actions_parse() {
+        let action = |text| Term::parse(text).and_then(|term| term.action());
+        assert_eq!(
+            action("Attack(EnemyNatural)"),
+            Ok(Some(BuildOrderAction::Attack(MapTarget::EnemyNatural)))
+        );
+        assert_eq!(
+            action("SetRally(Gateway, OurNatural)"),
+            Ok(Some(BuildOrderAction::SetRally(
+                UnitTypeId::Gateway,
+                MapTarget::OurNatural
+            )))
+        );
+        assert_eq!(
+            action("SendScout(Probe)"),
+            Ok(Some(BuildOrderAction::SendScout(UnitTypeId::Probe)))
+        );
+        assert_eq!(
+            action("Defend(TheirHouse)"),
+            Err(ParseProblem::UnknownName(
+                "map target",
+                "TheirHouse".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn upgrade_targets_parse() {
+        let action = |text| Term::parse(text).and_then(|term| term.action());
+        assert_eq!(
+            action("UpgradeTo(GroundWeapons, 3)"),
+            Ok(Some(BuildOrderAction::UpgradeTo(
+                UpgradeLine::GroundWeapons,
+                3
+            )))
+        );
+        assert_eq!(
+            action("UpgradeTo(Shields, lots)"),
+            Err(ParseProblem::ExpectedNumber("lots".to_string()))
+        );
+    }
+
+    #[test]
+    fn composition_parses() {
+        let action = |text| Term::parse(text).and_then(|term| term.action());
+        assert_eq!(
+            action("Produce(Stalker, Percent(30))"),
+            Ok(Some(BuildOrderAction::Produce(
+                UnitTypeId::Stalker,
+                Quota::Percent(30)
+            )))
+        );
+        assert_eq!(
+            action("Produce(Immortal, Count(2))"),
+            Ok(Some(BuildOrderAction::Produce(
+                UnitTypeId::Immortal,
+                Quota::Count(2)
+            )))
+        );
+        assert_eq!(
+            action("Produce(Zealot, Half)"),
+            Err(ParseProblem::UnknownName("quota", "Half".to_string()))
+        );
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        let bad_unit = "ok | do: Expand\n-bad | do: Construct(Gatway)";
+        assert!(matches!(
+            BuildOrderTree::parse(bad_unit),
+            Err(BuildFileError::Syntax {
+                line: 2,
+                problem: ParseProblem::UnknownName("unit type", _)
+            })
+        ));
+
+        let too_deep = "ok | do: Expand\n\n--too deep";
+        assert!(matches!(
+            BuildOrderTree::parse(too_deep),
+            Err(BuildFileError::BadNesting {
+                line: 3,
+                depth: 2,
+                deepest: 1
+            })
+        ));
+
+        let wrong_args = "bad | done when: All(SupplyAtLeast(1, 2))";
+        assert!(matches!(
+            BuildOrderTree::parse(wrong_args),
+            Err(BuildFileError::Syntax {
+                line: 1,
+                problem: ParseProblem::WrongArgumentCount { .. }
+            })
+        ));
+    }
+
+    #[test]
+    fn top_level_nodes_sit_next_to_root() {
+        let text = "opener | do: Expand\nunits | hidden | top level\n-zealots | do: Train(Zealot, GatewayTrainZealot)";
+        let tree = BuildOrderTree::parse(text);
+        assert!(tree.is_ok(), "{:?}", tree.err());
+        assert_eq!(
+            tree.unwrap().to_string(),
+            "ROOT➖\n-opener➖\nunits➖\n-zealots➖\n"
+        );
+
+        let nested = "opener | do: Expand\n-units | top level";
+        assert!(matches!(
+            BuildOrderTree::parse(nested),
+            Err(BuildFileError::Syntax {
+                line: 2,
+                problem: ParseProblem::NestedTopLevel
+            })
+        ));
+    }
+
+    #[test]
+    fn checked_in_build_matches_code() {
+        let from_file = BuildOrderTree::from_name("nexus_first_two_base_charge");
+        assert!(from_file.is_ok(), "{}", from_file.err().unwrap());
+        let from_code = BuildOrderTree::nexus_first_two_base_charge().unwrap();
+        assert_eq!(from_file.unwrap().to_string(), from_code.to_string());
+    }
+}
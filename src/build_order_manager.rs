@@ -1,30 +1,91 @@
-use std::f32::consts::TAU;
+use std::collections::HashSet;
 
 use itertools::Either;
-use rust_sc2::{game_state::PsionicMatrix, prelude::*};
+use rust_sc2::prelude::*;
 
 use crate::{
-    build_orders::{BuildCondition, BuildOrderAction},
+    army::{MissionType, UnitState},
+    budget::{fund_actions, Budget},
+    build_orders::{BuildCondition, BuildOrderAction, MapTarget},
+    build_timeline::Moment,
+    build_tree::EvaluatesConditions,
     errors::{BuildError, BuildingTransitionError},
+    production::train_ability,
     protoss_bot::{BotState, ReBiCycler},
+    upgrades::{plan_upgrades, upgrade_targets},
 };
 
 impl ReBiCycler {
     pub fn step_build(&mut self) {
         let doable_components = self.update_build();
-        for action in doable_components
+        let funded = fund_actions(
+            &doable_components,
+            self.spending_money(),
+            |action| self.action_cost(action),
+            |action| self.can_do_build_action(action),
+        );
+        // what's left after this step's actions, for warping in more than one at a time
+        let mut spare = self.spending_money();
+        for action in &funded {
+            spare.spend(self.action_cost(*action));
+        }
+        let upgrades = upgrade_targets(&funded);
+        let mut production_money = Budget::default();
+        for action in &funded {
+            if matches!(action, BuildOrderAction::Produce(_, _)) {
+                production_money.add(self.action_cost(*action));
+            }
+        }
+        for action in funded {
+            self.attempt_build_action(action, &mut spare);
+        }
+        self.run_upgrades(&upgrades);
+        // the composition gets what its nodes were funded for, and whatever nothing else wanted
+        self.production.read_build(&doable_components);
+        production_money.add(spare);
+        self.run_production(&mut production_money);
+        self.nexus_energy.read_build(&doable_components);
+        self.spend_nexus_energy();
+        self.end_scouting(&doable_components);
+    }
+
+    /// what's in the bank, minus what builders walking to a site will spend when they get there
+    fn spending_money(&self) -> Budget {
+        let mut budget = Budget::new(self.minerals, self.vespene);
+        for structure in self
+            .units
+            .my
+            .workers
             .iter()
-            .filter_map(super::build_tree::BuildComponent::action)
+            .filter_map(|worker| crate::ability_builds(worker.order()?.0))
         {
-            if self.can_do_build_action(action) {
-                self.attempt_build_action(action);
-            }
+            budget.spend(self.action_cost(BuildOrderAction::Construct(structure)));
         }
+        budget
+    }
+
+    fn action_cost(&self, action: BuildOrderAction) -> Budget {
+        let cost = match action {
+            BuildOrderAction::Train(unit_type, _)
+            | BuildOrderAction::Construct(unit_type)
+            | BuildOrderAction::Produce(unit_type, _) => self.get_unit_cost(unit_type),
+            BuildOrderAction::Expand => self.get_unit_cost(UnitTypeId::Nexus),
+            BuildOrderAction::Research(upgrade, _, _) => self.get_upgrade_cost(upgrade),
+            BuildOrderAction::UpgradeTo(line, level) => {
+                match line.next(level, &self.upgrade_state()) {
+                    Some(upgrade) => self.get_upgrade_cost(upgrade),
+                    None => return Budget::default(),
+                }
+            }
+            _ => return Budget::default(),
+        };
+        Budget::new(cost.minerals, cost.vespene)
     }
 
+    /// Whether the action could happen at all. Money is handled by `fund_actions`.
     fn can_do_build_action(&self, action: BuildOrderAction) -> bool {
         match action {
-            BuildOrderAction::Expand => self.can_afford(UnitTypeId::Nexus, false),
+            BuildOrderAction::Expand => !self.units.my.workers.is_empty(),
             BuildOrderAction::Chrono(_) => self
                 .units
                 .my
@@ -45,20 +106,27 @@ impl ReBiCycler {
                         .of_type(trainer)
                         .any(rust_sc2::prelude::Unit::is_active)
             }
-            BuildOrderAction::Construct(building) => {
-                let afford = self.can_afford(building, true);
-                let has_worker = !self.units.my.workers.is_empty();
-                afford && has_worker
+            BuildOrderAction::Construct(_) => !self.units.my.workers.is_empty(),
+            BuildOrderAction::Research(_, _, reseacher) => !self
+                .units
+                .my
+                .structures
+                .of_type(reseacher)
+                .idle()
+                .is_empty(),
+            BuildOrderAction::Produce(unit_type, _) => {
+                let now = self.time;
+                self.units.my.structures.ready().iter().any(|s| {
+                    let free = if s.type_id() == UnitTypeId::WarpGate {
+                        self.warp_gates.is_ready(s.tag(), now)
+                    } else {
+                        s.is_idle()
+                    };
+                    free && train_ability(s.type_id(), unit_type).is_some_and(|a| s.has_ability(a))
+                })
             }
-            BuildOrderAction::Research(upgrade, _, reseacher) => {
-                !self
-                    .units
-                    .my
-                    .structures
-                    .of_type(reseacher)
-                    .idle()
-                    .is_empty()
-                    && self.can_afford_upgrade(upgrade)
+            BuildOrderAction::UpgradeTo(line, level) => {
+                !plan_upgrades(&[(line, level)], &self.upgrade_state()).is_empty()
             }
             BuildOrderAction::Train(_, ability) => {
                 let has_trainer = self
@@ -71,47 +139,38 @@ impl ReBiCycler {
             }
             BuildOrderAction::Surrender => true,
             BuildOrderAction::Chat(_) => true,
-        }
-    }
-
-    pub fn evaluate_condition(&self, condition: &BuildCondition) -> bool {
-        match condition {
-            BuildCondition::DontHaveAnyDone(unit) => self.counter().count(*unit) == 0,
-            BuildCondition::DontHaveAnyStarted(unit) => self.counter().ordered().count(*unit) == 0,
-            BuildCondition::SupplyAtLeast(supply) => self.supply_used >= *supply,
-            BuildCondition::SupplyBetween(low, high) => {
-                self.supply_used >= *low && self.supply_used < *high
-            }
-            BuildCondition::LessThanCount(unit_type, desired_count) => {
-                let unit_count = self.counter().all().count(*unit_type);
-                unit_count < *desired_count
+            BuildOrderAction::Attack(_) | BuildOrderAction::Defend(_) => {
+                self.units.my.units.iter().any(|u| !u.is_worker())
             }
-            BuildCondition::SupplyLeftBelow(remaining_supply) => {
-                self.supply_left < *remaining_supply
-            }
-            BuildCondition::StructureComplete(structure_type) => {
-                self.units
-                    .my
-                    .structures
-                    .of_type(*structure_type)
-                    .iter()
-                    .ready()
-                    .count()
-                    > 0
-            }
-            BuildCondition::TechComplete(upgrade) => self.upgrade_progress(*upgrade) > 0.95,
-            BuildCondition::AtLeastCount(unit_type, desired_count) => {
-                self.counter().all().count(*unit_type) >= *desired_count
-            }
-            BuildCondition::Never => false,
-            BuildCondition::Always => true,
-            BuildCondition::TotalAndOrderedAtLeast(thing, count) => {
-                self.counter().all().count(*thing) >= *count
+            BuildOrderAction::SendScout(unit_type) => {
+                !self.army_manager.is_scouting(unit_type)
+                    && self
+                        .units
+                        .my
+                        .units
+                        .iter()
+                        .of_type(unit_type)
+                        .next()
+                        .is_some()
             }
+            BuildOrderAction::CancelStructure(structure) => self
+                .units
+                .my
+                .structures
+                .iter()
+                .of_type(structure)
+                .any(|s| !s.is_ready()),
+            BuildOrderAction::SetRally(structure, _) => self
+                .units
+                .my
+                .structures
+                .iter()
+                .of_type(structure)
+                .any(rust_sc2::prelude::Unit::is_ready),
         }
     }
 
-    fn attempt_build_action(&mut self, action: BuildOrderAction) {
+    fn attempt_build_action(&mut self, action: BuildOrderAction, spare: &mut Budget) {
         let result = match action {
             BuildOrderAction::Expand => {
                 let issues = self.update_building_obstructions();
@@ -130,15 +189,19 @@ impl ReBiCycler {
                     .collect();
                 self.build(unit_type)
             }
-            BuildOrderAction::Train(unit_type, ability) => self.train(unit_type, ability),
-            BuildOrderAction::Chrono(ability) => self.chrono_boost(ability),
-            BuildOrderAction::ChronoWhatever(trainer) => self.chrono_whatever(trainer),
+            BuildOrderAction::Train(unit_type, ability) => self.train(unit_type, ability, spare),
+            // the nexus energy manager reads these off the build and does the casting
+            BuildOrderAction::Chrono(_) | BuildOrderAction::ChronoWhatever(_) => Ok(()),
             BuildOrderAction::Research(upgrade, ability, researcher) => {
                 self.display_terminal.write_line_to_footer(&format!(
                     "Attempting build action to {ability:?} for {upgrade:?}"
                 ));
                 self.research(upgrade, ability, researcher)
             }
+            // run_production spreads these over the idle production
+            BuildOrderAction::Produce(_, _) => Ok(()),
+            // run_upgrades starts these all at once, so two lines don't both pick the same forge
+            BuildOrderAction::UpgradeTo(_, _) => Ok(()),
             BuildOrderAction::Surrender => {
                 self.bot_state = BotState::Surrendering(self.game_step());
 
@@ -148,11 +211,32 @@ impl ReBiCycler {
                 self.do_chat(chat);
                 Ok(())
             }
+            BuildOrderAction::Attack(target) => {
+                let rally = self.map_target(MapTarget::OurNatural);
+                let target = self.map_target(target);
+                self.launch_mission(MissionType::AttackEnemy(target), rally);
+                Ok(())
+            }
+            BuildOrderAction::Defend(target) => {
+                let target = self.map_target(target);
+                self.launch_mission(MissionType::DefendBase(target), target);
+                Ok(())
+            }
+            BuildOrderAction::SendScout(unit_type) => self.send_scout(unit_type),
+            BuildOrderAction::CancelStructure(structure) => self.cancel_structure(structure),
+            BuildOrderAction::SetRally(structure, target) => {
+                let target = self.map_target(target);
+                for building in self.units.my.structures.of_type(structure).ready().iter() {
+                    building.command(AbilityId::RallyUnits, Target::Pos(target), false);
+                }
+                Ok(())
+            }
         };
 
         if let Err(err) = result {
             match err {
-                BuildError::CantPlace(location, _type_id) => {
+                BuildError::CantPlace(location, _type_id)
+                | BuildError::WouldBlockPath(location, _type_id) => {
                     if let Err(err) = self.siting_director.mark_position_blocked(
                         location,
                         crate::siting::BuildingTransition::Obstruct,
@@ -162,12 +246,65 @@ impl ReBiCycler {
                         // bad location marked blocked, no problem.
                     }
                 }
-                BuildError::AllBusy(_) | BuildError::AllChronoed(_) => (),
+                BuildError::AllBusy(_) => (),
                 _ => self.unhandle_build(Either::Left(err), action),
             }
         }
     }
 
+    /// Takes a unit of the type off whatever it's doing and sends it to the enemy main.
+    fn send_scout(&mut self, unit_type: UnitTypeId) -> Result<(), BuildError> {
+        let enemy_main = self.map_target(MapTarget::EnemyMain);
+        // leave builders to their building, and take a probe off minerals before one off gas
+        let mineral_miners: HashSet<u64> = self.mining_manager.mineral_miners().collect();
+        let scout = self
+            .units
+            .my
+            .units
+            .of_type(unit_type)
+            .iter()
+            .filter(|unit| {
+                unit.order()
+                    .is_none_or(|(ability, _, _)| crate::ability_builds(ability).is_none())
+                    && !self.construction_manager.is_builder(unit.tag())
+            })
+            .min_by(|a, b| {
+                mineral_miners
+                    .contains(&b.tag())
+                    .cmp(&mineral_miners.contains(&a.tag()))
+                    .then_with(|| {
+                        crate::distance_squared(&a.position(), &enemy_main)
+                            .total_cmp(&crate::distance_squared(&b.position(), &enemy_main))
+                    })
+            })
+            .cloned()
+            .ok_or_else(|| BuildError::InvalidUnit(format!("No {unit_type:?} to scout with")))?;
+        if scout.is_worker() {
+            self.mining_manager.remove_worker(scout.tag());
+        }
+        let mission = self.new_mission(MissionType::Scout(enemy_main), scout.position());
+        self.army_manager
+            .assign_to(UnitState::from_unit(&scout), mission)
+            .map_err(|_| BuildError::InvalidUnit(format!("Couldn't send {unit_type:?} to scout")))
+    }
+
+    /// cancels the structure of the type that's furthest from done
+    fn cancel_structure(&self, structure: UnitTypeId) -> Result<(), BuildError> {
+        let building = self
+            .units
+            .my
+            .structures
+            .iter()
+            .of_type(structure)
+            .filter(|s| !s.is_ready())
+            .min_by(|a, b| a.build_progress().total_cmp(&b.build_progress()))
+            .ok_or_else(|| {
+                BuildError::InvalidUnit(format!("No {structure:?} under construction to cancel"))
+            })?;
+        building.cancel_building(false);
+        Ok(())
+    }
+
     pub fn unhandle_build(
         &mut self,
         err: Either<BuildError, BuildingTransitionError>,
@@ -179,7 +316,12 @@ impl ReBiCycler {
             .write_line_to_pane("Errors", &message, true);
     }
 
-    fn train(&self, unit_type: UnitTypeId, ability: AbilityId) -> Result<(), BuildError> {
+    fn train(
+        &mut self,
+        unit_type: UnitTypeId,
+        ability: AbilityId,
+        spare: &mut Budget,
+    ) -> Result<(), BuildError> {
         let mut trainers = self
             .units
             .my
@@ -193,121 +335,13 @@ impl ReBiCycler {
             .ok_or(BuildError::AllBusy(ability))?;
 
         if trainer.type_id() == UnitTypeId::WarpGate {
-            self.warp_in(unit_type, trainer)
+            self.warp_in(unit_type, ability, spare)
         } else {
             trainer.train(unit_type, false);
             Ok(())
         }
     }
 
-    fn warp_in(&self, unit_type: UnitTypeId, warpgate: &Unit) -> Result<(), BuildError> {
-        let unit_width = 2.0;
-        let booster_structures = self.units.my.all.of_types(&vec![
-            UnitTypeId::Pylon,
-            UnitTypeId::Nexus,
-            UnitTypeId::WarpPrismPhasing,
-        ]);
-
-        let is_fast = |matrix: &&PsionicMatrix| {
-            !booster_structures
-                .closer(matrix.radius, matrix.pos)
-                .is_empty()
-        };
-
-        let fast_warpins = self
-            .state
-            .observation
-            .raw
-            .psionic_matrix
-            .iter()
-            .filter(is_fast);
-
-        for matrix in fast_warpins {
-            if self
-                .warp_spot_spiral_search(matrix, warpgate, unit_type, unit_width)
-                .is_ok()
-            {
-                return Ok(());
-            }
-        }
-        Err(BuildError::NoPlacementLocations)
-    }
-
-    #[allow(clippy::cast_possible_truncation)]
-    #[allow(clippy::cast_sign_loss)]
-    #[allow(clippy::cast_precision_loss)]
-    fn warp_spot_spiral_search(
-        &self,
-        matrix: &PsionicMatrix,
-        warpgate: &Unit,
-        unit_type: UnitTypeId,
-        unit_width: f32,
-    ) -> Result<(), BuildError> {
-        let possible_rings = (matrix.radius / unit_width).floor();
-        for ring_number in 1..possible_rings as u64 {
-            let circumference = TAU * matrix.radius * (ring_number as f32 / possible_rings);
-            let possible_angles = (circumference / unit_width).floor();
-            for angle_step in 0..possible_angles as usize {
-                let angle = TAU * angle_step as f32 / possible_angles;
-                let offset = Point2::new(ring_number as f32 * unit_width, 0.0).rotate(angle);
-
-                let spot = matrix.pos + offset;
-                if self.is_pathable(spot) && self.is_placeable(spot) {
-                    warpgate.warp_in(unit_type, spot);
-                    return Ok(());
-                }
-            }
-        }
-        Err(BuildError::NoPlacementLocations)
-    }
-    fn chrono_whatever(&self, trainer: UnitTypeId) -> Result<(), BuildError> {
-        let nexus = self
-            .units
-            .my
-            .structures
-            .iter()
-            .find(|unit| unit.has_ability(AbilityId::EffectChronoBoostEnergyCost))
-            .ok_or(BuildError::CantAfford)?;
-        let target = self
-            .units
-            .my
-            .structures
-            .iter()
-            .of_type(trainer)
-            .find(|s| s.is_active() && !s.has_buff(BuffId::ChronoBoostEnergyCost))
-            .ok_or(BuildError::AllChronoed(AbilityId::YamatoYamatoGun))?;
-
-        nexus.command(
-            AbilityId::EffectChronoBoostEnergyCost,
-            Target::Tag(target.tag()),
-            false,
-        );
-        Ok(())
-    }
-    fn chrono_boost(&self, ability: AbilityId) -> Result<(), BuildError> {
-        let nexus = self
-            .units
-            .my
-            .structures
-            .iter()
-            .find(|unit| unit.has_ability(AbilityId::EffectChronoBoostEnergyCost))
-            .ok_or(BuildError::CantAfford)?;
-        let target = self
-            .units
-            .my
-            .structures
-            .iter()
-            .find(|s| s.is_using(ability) && !s.has_buff(BuffId::ChronoBoostEnergyCost))
-            .ok_or(BuildError::AllChronoed(ability))?;
-
-        nexus.command(
-            AbilityId::EffectChronoBoostEnergyCost,
-            Target::Tag(target.tag()),
-            false,
-        );
-        Ok(())
-    }
-
     /// Finds a structure to do the research, and then does so.
     ///
     /// # Errors
@@ -342,6 +376,97 @@ impl ReBiCycler {
     }
 }
 
+impl EvaluatesConditions for ReBiCycler {
+    fn evaluate_condition(&self, condition: &BuildCondition) -> bool {
+        match condition {
+            BuildCondition::DontHaveAnyDone(unit) => self.counter().count(*unit) == 0,
+            BuildCondition::DontHaveAnyStarted(unit) => self.counter().ordered().count(*unit) == 0,
+            BuildCondition::SupplyAtLeast(supply) => self.supply_used >= *supply,
+            BuildCondition::SupplyBetween(low, high) => {
+                self.supply_used >= *low && self.supply_used < *high
+            }
+            BuildCondition::LessThanCount(unit_type, desired_count) => {
+                let unit_count = self.counter().all().count(*unit_type);
+                unit_count < *desired_count
+            }
+            BuildCondition::SupplyLeftBelow(remaining_supply) => {
+                self.supply_left < *remaining_supply
+            }
+            BuildCondition::StructureComplete(structure_type) => {
+                self.units
+                    .my
+                    .structures
+                    .of_type(*structure_type)
+                    .iter()
+                    .ready()
+                    .count()
+                    > 0
+            }
+            BuildCondition::TechComplete(upgrade) => self.upgrade_progress(*upgrade) > 0.95,
+            BuildCondition::AtLeastCount(unit_type, desired_count) => {
+                self.counter().all().count(*unit_type) >= *desired_count
+            }
+            BuildCondition::Never => false,
+            BuildCondition::Always => true,
+            BuildCondition::TotalAndOrderedAtLeast(thing, count) => {
+                self.counter().all().count(*thing) >= *count
+            }
+            BuildCondition::EnemyRaceIs(race) => self.knowledge.confirmed_enemy_race == Some(*race),
+            BuildCondition::EnemyUnitSeen(unit_type) => {
+                self.knowledge.first_seen_unit_times.contains_key(unit_type)
+            }
+            BuildCondition::EnemyUnitSeenBefore(unit_type, frame) => self
+                .knowledge
+                .first_seen_unit_times
+                .get(unit_type)
+                .is_some_and(|seen| seen < frame),
+            BuildCondition::EnemyStructureCountAtLeast(structure_type, count) => {
+                self.knowledge.count_enemy_structures(*structure_type) >= *count
+            }
+            BuildCondition::EnemyExpansionsAtLeast(count) => {
+                self.knowledge.count_enemy_expansions(self.enemy_start) >= *count
+            }
+            #[allow(clippy::cast_precision_loss)]
+            BuildCondition::GameTimeAtLeast(seconds) => self.time >= *seconds as f32,
+            BuildCondition::MineralsAtLeast(amount) => self.minerals >= *amount,
+            BuildCondition::MineralsBelow(amount) => self.minerals < *amount,
+            BuildCondition::GasAtLeast(amount) => self.vespene >= *amount,
+            BuildCondition::GasBelow(amount) => self.vespene < *amount,
+            BuildCondition::BasesSaturatedAtLeast(count) => {
+                self.mining_manager.saturated_bases() >= *count
+            }
+            BuildCondition::IdleProductionAtLeast(structure_type, count) => {
+                let now = self.time;
+                self.units
+                    .my
+                    .structures
+                    .of_type(*structure_type)
+                    .ready()
+                    .iter()
+                    .filter(|s| {
+                        if s.type_id() == UnitTypeId::WarpGate {
+                            self.warp_gates.is_ready(s.tag(), now)
+                        } else {
+                            s.is_idle()
+                        }
+                    })
+                    .count()
+                    >= *count
+            }
+        }
+    }
+
+    fn moment(&self) -> Moment {
+        Moment {
+            frame: self.state.observation.game_loop() as usize,
+            time: self.time,
+            supply: self.supply_used,
+            minerals: self.minerals,
+            gas: self.vespene,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -1,8 +1,11 @@
 use std::fmt::Display;
 
-use rust_sc2::prelude::{AbilityId, UnitTypeId, UpgradeId};
+use rust_sc2::{
+    player::Race,
+    prelude::{AbilityId, UnitTypeId, UpgradeId},
+};
 
-use crate::chatter::ChatAction;
+use crate::{chatter::ChatAction, production::Quota, upgrades::UpgradeLine};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BuildCondition {
@@ -18,6 +21,25 @@ pub enum BuildCondition {
     Always,
     Never,
     TotalAndOrderedAtLeast(UnitTypeId, usize),
+    /// what we know of the opponent's race, once they're not Random anymore
+    EnemyRaceIs(Race),
+    EnemyUnitSeen(UnitTypeId),
+    /// we'd seen this enemy unit type before this frame
+    EnemyUnitSeenBefore(UnitTypeId, usize),
+    /// counts the enemy structures we've seen and haven't seen die
+    EnemyStructureCountAtLeast(UnitTypeId, usize),
+    /// townhalls we've seen away from the enemy's main
+    EnemyExpansionsAtLeast(usize),
+    /// seconds of game time
+    GameTimeAtLeast(u32),
+    MineralsAtLeast(u32),
+    MineralsBelow(u32),
+    GasAtLeast(u32),
+    GasBelow(u32),
+    /// bases with every mineral and gas job filled
+    BasesSaturatedAtLeast(usize),
+    /// this many of the production structure are sitting without orders
+    IdleProductionAtLeast(UnitTypeId, usize),
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BuildOrderAction {
@@ -26,9 +48,44 @@ pub enum BuildOrderAction {
     Chrono(AbilityId),
     ChronoWhatever(UnitTypeId),
     Research(UpgradeId, AbilityId, UnitTypeId),
+    /// a part of the army composition, production is spread over every part that's active
+    Produce(UnitTypeId, Quota),
+    /// keeps researching the line, on whichever researchers are free, until it's at the level
+    UpgradeTo(UpgradeLine, u8),
     Expand,
     Chat(ChatAction),
     Surrender,
+    /// sends the army to attack-move on the target, new units join as they come out
+    Attack(MapTarget),
+    /// keeps the army at home around the target
+    Defend(MapTarget),
+    /// sends one unit of the type to look at the enemy's main
+    SendScout(UnitTypeId),
+    /// cancels the least finished structure of the type, for getting our money back
+    CancelStructure(UnitTypeId),
+    /// points every ready structure of the type at the target
+    SetRally(UnitTypeId, MapTarget),
+}
+
+/// A place a build can name before it knows what map it's on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapTarget {
+    OurMain,
+    OurNatural,
+    EnemyMain,
+    EnemyNatural,
+    MapCenter,
+}
+
+/// How much a component's action matters when money is short.
+/// Higher priorities get paid first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
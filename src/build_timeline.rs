@@ -0,0 +1,145 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, Write},
+};
+
+use crate::build_orders::ComponentState;
+
+/// Where timelines are saved at the end of a game, next to the build results
+pub const TIMELINE_DIRECTORY: &str = "replays";
+
+/// The game as it was when something in the build changed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Moment {
+    pub frame: usize,
+    /// seconds of game time
+    pub time: f32,
+    pub supply: u32,
+    pub minerals: u32,
+    pub gas: u32,
+}
+
+/// One node of the build moving from one state to another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub moment: Moment,
+    pub node: usize,
+    pub name: String,
+    pub from: ComponentState,
+    pub to: ComponentState,
+}
+
+/// Every state change the build went through this game, oldest first.
+/// Saved next to the history at the end of the game so two games can be diffed.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    transitions: Vec<Transition>,
+}
+
+impl Timeline {
+    pub fn record(
+        &mut self,
+        moment: Moment,
+        node: usize,
+        name: &str,
+        from: ComponentState,
+        to: ComponentState,
+    ) {
+        self.transitions.push(Transition {
+            moment,
+            node,
+            name: name.to_string(),
+            from,
+            to,
+        });
+    }
+
+    pub fn transitions(&self) -> &[Transition] {
+        &self.transitions
+    }
+
+    /// the first time the node went into `state`
+    pub fn first(&self, node: usize, state: &ComponentState) -> Option<&Transition> {
+        self.transitions
+            .iter()
+            .find(|t| t.node == node && &t.to == state)
+    }
+
+    /// one line per transition, with a header
+    pub fn to_csv(&self) -> String {
+        let mut out = "frame,time,supply,minerals,gas,node,name,from,to\n".to_string();
+        for t in &self.transitions {
+            let _ = writeln!(
+                out,
+                "{},{:.1},{},{},{},{},\"{}\",{:?},{:?}",
+                t.moment.frame,
+                t.moment.time,
+                t.moment.supply,
+                t.moment.minerals,
+                t.moment.gas,
+                t.node,
+                t.name.replace('"', "\"\""),
+                t.from,
+                t.to
+            );
+        }
+        out
+    }
+
+    /// # Errors
+    /// Any io error from writing the file.
+    pub fn save_csv(&self, filename: &str) -> io::Result<()> {
+        let mut output = File::create(filename)?;
+        write!(output, "{}", self.to_csv())
+    }
+}
+
+/// One file per game, named after the build and when the game ended,
+/// so games don't overwrite each other and can be diffed per build.
+pub fn timeline_filename(build: &str, seconds: u64) -> String {
+    format!("{TIMELINE_DIRECTORY}/{build}_{seconds}.csv")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_tree::BuildOrderTree;
+
+    #[test]
+    fn simulated_build_leaves_a_trail() {
+        let report = BuildOrderTree::nexus_first_two_base_charge()
+            .unwrap()
+            .simulate(300.0);
+        let timeline = &report.timeline;
+
+        // recorded in the order they happened
+        for window in timeline.transitions().windows(2) {
+            assert!(window[0].moment.frame <= window[1].moment.frame);
+        }
+        for timing in &report.timings {
+            if let Some(activated) = timing.activated {
+                let started = timeline
+                    .first(timing.node, &ComponentState::Active)
+                    .or_else(|| timeline.first(timing.node, &ComponentState::Completed))
+                    .expect("activated nodes are in the timeline");
+                assert!((started.moment.time - activated).abs() < f32::EPSILON);
+            }
+        }
+
+        let csv = timeline.to_csv();
+        assert!(csv.starts_with("frame,time,supply,minerals,gas,node,name,from,to\n"));
+        assert_eq!(csv.lines().count(), timeline.transitions().len() + 1);
+    }
+
+    #[test]
+    fn each_game_gets_its_own_file() {
+        let first = timeline_filename("nexus_first_two_base_charge", 1_700_000_000);
+        assert_eq!(first, "replays/nexus_first_two_base_charge_1700000000.csv");
+        assert_ne!(
+            first,
+            timeline_filename("nexus_first_two_base_charge", 1_700_000_060)
+        );
+        assert_ne!(first, timeline_filename("proxy_gates", 1_700_000_000));
+    }
+}
@@ -1,26 +1,47 @@
-use std::fmt::Display;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+};
 
 use crate::{
-    build_orders::{BuildCondition, BuildOrderAction, ComponentState},
+    build_orders::{BuildCondition, BuildOrderAction, ComponentState, Priority},
+    build_timeline::{Moment, Timeline},
     protoss_bot::ReBiCycler,
 };
 
-use ego_tree::Tree;
+use ego_tree::{NodeId, NodeRef, Tree};
+use rust_sc2::ids::UnitTypeId;
 
 pub struct BuildOrderTree {
     pub(crate) tree: Tree<BuildComponent>,
+    /// node ids by index, in insertion order
+    ids: Vec<NodeId>,
+    indexes: HashMap<NodeId, usize>,
+    /// every state change so far, for saving at the end of the game
+    pub timeline: Timeline,
 }
 
 impl Default for BuildOrderTree {
     fn default() -> Self {
         let tree = Tree::new(BuildComponent {
+            start_when: ConditionGroup::new(&[], ConditionOperator::All),
             complete_when: ConditionGroup::new(&[], ConditionOperator::All),
             name: "ROOT".to_string(),
             action: None,
             state: ComponentState::NotYetStarted,
             display: false,
+            priority: Priority::default(),
+            repeats: false,
+            completed_before: false,
+            reaction: false,
         });
-        Self { tree }
+        let root = tree.root().id();
+        Self {
+            tree,
+            ids: vec![root],
+            indexes: HashMap::from([(root, 0)]),
+            timeline: Timeline::default(),
+        }
     }
 }
 /// An element of the build order tree.
@@ -31,6 +52,8 @@ pub struct BuildComponent {
     /// BuildComponents with None actions are checkpoints that can
     /// unleash reactions, i think
     action: Option<BuildOrderAction>,
+    /// Conditions that must hold, once the parent is done, for this node to activate
+    start_when: ConditionGroup,
     /// Conditions that end the activation of this node
     complete_when: ConditionGroup,
     /// A friendly name for the node
@@ -39,44 +62,179 @@ pub struct BuildComponent {
     display: bool,
     /// A state to measure this thing's status
     state: ComponentState,
+    /// who gets paid first when several components want money
+    priority: Priority,
+    /// goes back to active whenever its done conditions stop holding,
+    /// like a quota that gets topped up after losses
+    repeats: bool,
+    /// a repeating node's children don't wait on it again once it's been done
+    completed_before: bool,
+    /// when this node starts, the rest of its parent's branches are restricted
+    /// and its own branch gets paid first. For cheese and other emergencies.
+    reaction: bool,
 }
 impl BuildComponent {
     pub fn new(
         name: &str,
+        start: ConditionGroup,
         end: ConditionGroup,
         action: Option<BuildOrderAction>,
         display: bool,
     ) -> Self {
         Self {
+            start_when: start,
             complete_when: end,
             name: name.to_string(),
             action,
             state: ComponentState::NotYetStarted,
             display,
+            priority: Priority::default(),
+            repeats: false,
+            completed_before: false,
+            reaction: false,
         }
     }
+
+    /// Keeps `count` of `unit_type` around, training more with `action` whenever we drop below.
+    pub fn quota(
+        name: &str,
+        start: ConditionGroup,
+        unit_type: UnitTypeId,
+        count: usize,
+        action: BuildOrderAction,
+    ) -> Self {
+        Self::new(
+            name,
+            start,
+            ConditionGroup::new(
+                &[BuildCondition::TotalAndOrderedAtLeast(unit_type, count)],
+                ConditionOperator::All,
+            ),
+            Some(action),
+            true,
+        )
+        .repeating()
+    }
+    #[must_use]
+    pub const fn repeating(mut self) -> Self {
+        self.repeats = true;
+        self
+    }
+    pub const fn repeats(&self) -> bool {
+        self.repeats
+    }
+    #[must_use]
+    pub const fn reacting(mut self) -> Self {
+        self.reaction = true;
+        self
+    }
+    pub const fn is_reaction(&self) -> bool {
+        self.reaction
+    }
+    #[must_use]
+    pub const fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+    pub const fn priority(&self) -> Priority {
+        self.priority
+    }
+    /// a node that does `action` straight away, for tests that only care about the action
+    #[cfg(test)]
+    pub fn doing(action: BuildOrderAction, priority: Priority) -> Self {
+        Self::new(
+            "test",
+            ConditionGroup::new(&[], ConditionOperator::All),
+            ConditionGroup::new(&[], ConditionOperator::All),
+            Some(action),
+            true,
+        )
+        .with_priority(priority)
+    }
     pub const fn action(&self) -> Option<BuildOrderAction> {
         self.action
     }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub const fn start_when(&self) -> &ConditionGroup {
+        &self.start_when
+    }
+    pub const fn complete_when(&self) -> &ConditionGroup {
+        &self.complete_when
+    }
+    pub const fn state(&self) -> &ComponentState {
+        &self.state
+    }
 }
 
-/// Groups conditions using the logical operator
-#[derive(Clone)]
+/// Groups conditions using the logical operator.
+/// Groups can hold other groups, so "(A and B) or C" is
+/// `Any` over an `All(A, B)` group and C.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConditionGroup {
-    pub conditions: Vec<BuildCondition>,
+    pub terms: Vec<ConditionTerm>,
     pub operator: ConditionOperator,
 }
 impl ConditionGroup {
     pub fn new(conditions: &[BuildCondition], operator: ConditionOperator) -> Self {
         Self {
-            conditions: conditions.to_vec(),
+            terms: conditions
+                .iter()
+                .cloned()
+                .map(ConditionTerm::Condition)
+                .collect(),
             operator,
         }
     }
+
+    pub const fn nested(terms: Vec<ConditionTerm>, operator: ConditionOperator) -> Self {
+        Self { terms, operator }
+    }
+
+    /// adds a whole group as one more term of this one
+    #[must_use]
+    pub fn with_group(mut self, group: Self) -> Self {
+        self.terms.push(ConditionTerm::Group(group));
+        self
+    }
+}
+
+impl Display for ConditionGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut terms: Vec<String> = self.terms.iter().map(ToString::to_string).collect();
+        let name = match self.operator {
+            ConditionOperator::All => "All",
+            ConditionOperator::NotAll => "NotAll",
+            ConditionOperator::Any => "Any",
+            ConditionOperator::NoneOf => "NoneOf",
+            ConditionOperator::ExactlyNOf(n) => {
+                terms.insert(0, n.to_string());
+                "ExactlyNOf"
+            }
+        };
+        write!(f, "{name}({})", terms.join(", "))
+    }
+}
+
+/// One term of a `ConditionGroup`, either a single condition or a nested group
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConditionTerm {
+    Condition(BuildCondition),
+    Group(ConditionGroup),
+}
+
+impl Display for ConditionTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Condition(condition) => write!(f, "{condition:?}"),
+            Self::Group(group) => write!(f, "{group}"),
+        }
+    }
 }
 
 /// Operator for logically combining `BuildConditions`
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ConditionOperator {
     All,
     NotAll,
@@ -92,44 +250,366 @@ impl BuildOrderTree {
         component: BuildComponent,
         parent: Option<usize>,
     ) -> Result<usize, TreeError> {
-        todo!()
+        let id = if let Some(parent) = parent {
+            let parent_id = self.node_id(parent).ok_or(TreeError::NodeNotInTree)?;
+            self.tree
+                .get_mut(parent_id)
+                .ok_or(TreeError::NodeNotInTree)?
+                .append(component)
+                .id()
+        } else {
+            self.tree.orphan(component).id()
+        };
+        Ok(self.track(id))
+    }
+
+    /// hands out the next index to a node that was just put in the tree
+    fn track(&mut self, id: NodeId) -> usize {
+        let index = self.ids.len();
+        self.ids.push(id);
+        self.indexes.insert(id, index);
+        index
+    }
+
+    /// puts a new node between `node` and its parent, so `node` waits on it.
+    /// Returns the new node's index, the old indexes don't change.
+    pub fn insert_above(
+        &mut self,
+        node: usize,
+        component: BuildComponent,
+    ) -> Result<usize, TreeError> {
+        let id = self.node_id(node).ok_or(TreeError::NodeNotInTree)?;
+        let mut below = self.tree.get_mut(id).ok_or(TreeError::NodeNotInTree)?;
+        let new_id = if below.parent().is_some() {
+            below.insert_before(component).id()
+        } else {
+            self.tree.orphan(component).id()
+        };
+        self.tree
+            .get_mut(new_id)
+            .ok_or(TreeError::NodeNotInTree)?
+            .append_id(id);
+        Ok(self.track(new_id))
     }
 
     pub fn get(&self, node: usize) -> Option<&BuildComponent> {
-        todo!()
+        self.tree.get(self.node_id(node)?).map(|n| n.value())
+    }
+
+    /// returns the index of a node's parent, if it has one.
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        let id = self.node_id(node)?;
+        let parent_id = self.tree.get(id)?.parent()?.id();
+        self.index_of(parent_id)
     }
 
     /// returns a vec of indexes for the
     /// tree in breadth first order
     pub fn breadth_first(&self) -> Vec<usize> {
-        todo!()
+        let mut queue: VecDeque<NodeRef<'_, BuildComponent>> = self.roots().collect();
+        let mut order = Vec::new();
+
+        while let Some(node) = queue.pop_front() {
+            if let Some(index) = self.index_of(node.id()) {
+                order.push(index);
+            }
+            queue.extend(node.children());
+        }
+        order
     }
 
     /// updates all descendants of node to restricted, recursively.
-    fn restrict_descendants(&mut self, of_node: usize) {
-        todo!()
+    fn restrict_descendants(&mut self, of_node: usize, moment: Moment) {
+        let Some(id) = self.node_id(of_node) else {
+            return;
+        };
+        let descendants: Vec<NodeId> = self
+            .tree
+            .get(id)
+            .map(|n| n.descendants().skip(1).map(|d| d.id()).collect())
+            .unwrap_or_default();
+
+        for descendant in descendants {
+            let index = self.index_of(descendant);
+            if let (Some(index), Some(mut node)) = (index, self.tree.get_mut(descendant)) {
+                let component = node.value();
+                if component.state != ComponentState::Restricted {
+                    self.timeline.record(
+                        moment,
+                        index,
+                        &component.name,
+                        component.state.clone(),
+                        ComponentState::Restricted,
+                    );
+                    component.state = ComponentState::Restricted;
+                }
+            }
+        }
     }
 
+    /// A reaction that just started takes over from its siblings:
+    /// their branches are restricted, apart from other reactions,
+    /// and everything under the reaction is paid for first.
+    fn preempt(&mut self, reaction: usize, moment: Moment) {
+        let Some(parent_id) = self.parent(reaction).and_then(|p| self.node_id(p)) else {
+            return;
+        };
+        let siblings: Vec<usize> = self
+            .tree
+            .get(parent_id)
+            .map(|p| p.children().filter_map(|c| self.index_of(c.id())).collect())
+            .unwrap_or_default();
+
+        for sibling in siblings {
+            if sibling == reaction {
+                continue;
+            }
+            let Some(component) = self.get_mut(sibling) else {
+                continue;
+            };
+            if component.reaction {
+                continue;
+            }
+            if !matches!(
+                component.state,
+                ComponentState::Completed | ComponentState::Restricted
+            ) {
+                let (name, old_state) = (component.name.clone(), component.state.clone());
+                component.state = ComponentState::Restricted;
+                self.timeline.record(
+                    moment,
+                    sibling,
+                    &name,
+                    old_state,
+                    ComponentState::Restricted,
+                );
+            }
+            self.restrict_descendants(sibling, moment);
+        }
+
+        let Some(id) = self.node_id(reaction) else {
+            return;
+        };
+        let branch: Vec<NodeId> = self
+            .tree
+            .get(id)
+            .map(|n| n.descendants().map(|d| d.id()).collect())
+            .unwrap_or_default();
+        for node in branch {
+            if let Some(mut node) = self.tree.get_mut(node) {
+                node.value().priority = Priority::Urgent;
+            }
+        }
+    }
+
+    /// ego_tree hands out values in insert order, which is the order we give out indexes in.
     fn get_mut(&mut self, index: usize) -> Option<&mut BuildComponent> {
-        todo!()
+        self.tree.values_mut().nth(index)
+    }
+
+    /// copies every node of `other` except its ROOT underneath `parent`,
+    /// keeping the shape and sibling order of `other`.
+    /// Other top level nodes in `other` stay top level.
+    /// Nodes are copied depth first, the order a build file lists them in,
+    /// so a build written in code and the same build read from a file get the same indexes.
+    pub fn graft(&mut self, other: &Self, parent: usize) -> Result<(), TreeError> {
+        let mut new_indexes = vec![parent; other.ids.len()];
+        let depth_first: Vec<usize> = other
+            .roots()
+            .flat_map(|root| root.descendants())
+            .filter_map(|node| other.index_of(node.id()))
+            .collect();
+        for index in depth_first.into_iter().skip(1) {
+            let component = other.get(index).ok_or(TreeError::NodeNotInTree)?;
+            let new_parent = other.parent(index).map(|p| new_indexes[p]);
+            new_indexes[index] = self.add_node(component.clone(), new_parent)?;
+        }
+        Ok(())
+    }
+
+    /// Nodes are never removed from the tree, so insertion order is a stable index.
+    fn node_id(&self, index: usize) -> Option<NodeId> {
+        self.ids.get(index).copied()
+    }
+
+    fn index_of(&self, id: NodeId) -> Option<usize> {
+        self.indexes.get(&id).copied()
+    }
+
+    /// every node without a parent, in insertion order
+    fn roots(&self) -> impl Iterator<Item = NodeRef<'_, BuildComponent>> {
+        self.ids
+            .iter()
+            .filter_map(|id| self.tree.get(*id))
+            .filter(|n| n.parent().is_none())
     }
 }
 
 impl Display for BuildOrderTree {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        todo!()
+        for root in self.roots() {
+            let mut stack = vec![(root, 0)];
+            while let Some((node, depth)) = stack.pop() {
+                let component = node.value();
+                writeln!(
+                    f,
+                    "{}{}{}{}",
+                    "-".repeat(depth),
+                    component.name,
+                    if component.repeats { "🔁" } else { "" },
+                    component.state
+                )?;
+                stack.extend(node.children().map(|child| (child, depth + 1)));
+            }
+        }
+        Ok(())
+    }
+}
+/// A fluent builder for build order trees.
+/// `root` starts a new branch off the ROOT, `child` goes one level deeper,
+/// and `leaf` hangs a node off the current one without moving.
+pub struct TreePointer {
+    pub tree: BuildOrderTree,
+    pointer: usize,
+}
+
+impl Default for TreePointer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TreePointer {
+    pub fn new() -> Self {
+        Self {
+            tree: BuildOrderTree::default(),
+            pointer: 0,
+        }
+    }
+
+    /// adds a node under ROOT and points at it
+    pub fn root(
+        self,
+        name: &str,
+        start: ConditionGroup,
+        end: ConditionGroup,
+        action: Option<BuildOrderAction>,
+        display: bool,
+    ) -> Result<Self, TreeError> {
+        Self { pointer: 0, ..self }.child(name, start, end, action, display)
+    }
+
+    /// adds a checkpoint under ROOT that completes immediately, and points at it
+    pub fn empty_root(self, name: Option<&str>) -> Result<Self, TreeError> {
+        self.root(
+            name.unwrap_or("checkpoint"),
+            ConditionGroup::new(&[], ConditionOperator::All),
+            ConditionGroup::new(&[], ConditionOperator::All),
+            None,
+            false,
+        )
+    }
+
+    /// adds a checkpoint that hangs off nothing, next to ROOT rather than under it,
+    /// so it runs alongside the rest of the build. Points at it.
+    pub fn top_level(mut self, name: Option<&str>) -> Result<Self, TreeError> {
+        let component = BuildComponent::new(
+            name.unwrap_or("checkpoint"),
+            ConditionGroup::new(&[], ConditionOperator::All),
+            ConditionGroup::new(&[], ConditionOperator::All),
+            None,
+            false,
+        );
+        self.pointer = self.tree.add_node(component, None)?;
+        Ok(self)
+    }
+
+    /// adds a node under the current node and points at the new one
+    pub fn child(
+        mut self,
+        name: &str,
+        start: ConditionGroup,
+        end: ConditionGroup,
+        action: Option<BuildOrderAction>,
+        display: bool,
+    ) -> Result<Self, TreeError> {
+        let component = BuildComponent::new(name, start, end, action, display);
+        self.pointer = self.tree.add_node(component, Some(self.pointer))?;
+        Ok(self)
+    }
+
+    /// adds a node under the current node without moving the pointer
+    pub fn leaf(
+        mut self,
+        name: &str,
+        start: ConditionGroup,
+        end: ConditionGroup,
+        action: Option<BuildOrderAction>,
+        display: bool,
+    ) -> Result<Self, TreeError> {
+        let component = BuildComponent::new(name, start, end, action, display);
+        self.tree.add_node(component, Some(self.pointer))?;
+        Ok(self)
+    }
+
+    /// sets the priority of the current node
+    pub fn prioritize(mut self, priority: Priority) -> Result<Self, TreeError> {
+        self.tree
+            .get_mut(self.pointer)
+            .ok_or(TreeError::NodeNotInTree)?
+            .priority = priority;
+        Ok(self)
+    }
+
+    /// makes the current node repeat
+    pub fn repeat(mut self) -> Result<Self, TreeError> {
+        self.tree
+            .get_mut(self.pointer)
+            .ok_or(TreeError::NodeNotInTree)?
+            .repeats = true;
+        Ok(self)
+    }
+
+    /// makes the current node a reaction that preempts its siblings
+    pub fn reaction(mut self) -> Result<Self, TreeError> {
+        self.tree
+            .get_mut(self.pointer)
+            .ok_or(TreeError::NodeNotInTree)?
+            .reaction = true;
+        Ok(self)
+    }
+
+    /// grafts another builder's branches under the current node without moving the pointer
+    pub fn subtree(mut self, other: Self) -> Result<Self, TreeError> {
+        self.tree.graft(&other.tree, self.pointer)?;
+        Ok(self)
     }
 }
+
 #[derive(Debug)]
 pub enum TreeError {
     TreeNotEmpty,
     NodeNotInTree,
 }
 
-impl ReBiCycler {
+/// Anything that can say whether a `BuildCondition` holds right now,
+/// like the bot in a live game or the offline simulator.
+pub trait EvaluatesConditions {
+    fn evaluate_condition(&self, condition: &BuildCondition) -> bool;
+
+    /// where the game is right now, for the build's timeline
+    fn moment(&self) -> Moment;
+
+    fn evaluate_condition_term(&self, term: &ConditionTerm) -> bool {
+        match term {
+            ConditionTerm::Condition(condition) => self.evaluate_condition(condition),
+            ConditionTerm::Group(group) => self.evaluate_condition_group(group),
+        }
+    }
+
     fn evaluate_condition_group(&self, condition_group: &ConditionGroup) -> bool {
-        let mut iter = condition_group.conditions.iter();
-        let evaluator = |c| self.evaluate_condition(c);
+        let mut iter = condition_group.terms.iter();
+        let evaluator = |t| self.evaluate_condition_term(t);
         match condition_group.operator {
             ConditionOperator::All => iter.all(evaluator),
             ConditionOperator::NotAll => !iter.all(evaluator),
@@ -140,22 +620,76 @@ impl ReBiCycler {
             }
         }
     }
+}
 
-    fn evaluate_build_component(&self, component: &BuildComponent) -> bool {
-        self.evaluate_condition_group(&component.complete_when)
-    }
+impl BuildOrderTree {
+    /// Moves a single node along its state machine.
+    /// A node may only start once its parent is complete and its own start conditions hold.
+    /// Restricted nodes pass their restriction down to their children.
+    /// Repeating nodes drop back to active when their done conditions stop holding,
+    /// but their children carry on as if they were still complete.
+    /// Reactions preempt their siblings as soon as they start.
+    fn update_component(
+        &mut self,
+        index: usize,
+        judge: &impl EvaluatesConditions,
+    ) -> Option<&BuildComponent> {
+        let parent_state = self.parent(index).and_then(|p| self.get(p)).map(|p| {
+            if p.completed_before && p.state == ComponentState::Active {
+                ComponentState::Completed
+            } else {
+                p.state.clone()
+            }
+        });
+        let component = self.get(index)?;
 
-    fn update_component(&mut self, index: usize) -> Option<&BuildComponent> {
-        let component = self.build_order.get(index)?;
-        let end = self.evaluate_build_component(&component);
-        let should_activate = !end;
+        let new_state = match (&component.state, parent_state) {
+            (ComponentState::Completed, _)
+                if component.repeats
+                    && !judge.evaluate_condition_group(&component.complete_when) =>
+            {
+                ComponentState::Active
+            }
+            (ComponentState::Completed, _) => ComponentState::Completed,
+            (ComponentState::Restricted, _) | (_, Some(ComponentState::Restricted)) => {
+                ComponentState::Restricted
+            }
+            (ComponentState::NotYetStarted, None | Some(ComponentState::Completed))
+                if !judge.evaluate_condition_group(&component.start_when) =>
+            {
+                ComponentState::NotYetStarted
+            }
+            (ComponentState::NotYetStarted, None | Some(ComponentState::Completed))
+            | (ComponentState::Active, _) => {
+                if judge.evaluate_condition_group(&component.complete_when) {
+                    ComponentState::Completed
+                } else {
+                    ComponentState::Active
+                }
+            }
+            (ComponentState::NotYetStarted, _) => ComponentState::NotYetStarted,
+        };
 
-        let node = self.build_order.get_mut(index)?;
-        if should_activate {
-            node.state = ComponentState::Active;
-        } else if end {
-            node.state = ComponentState::Completed;
+        if new_state != component.state {
+            let moment = judge.moment();
+            let (name, old_state) = (component.name.clone(), component.state.clone());
+            let fired = component.reaction
+                && old_state == ComponentState::NotYetStarted
+                && new_state != ComponentState::Restricted;
+            self.timeline
+                .record(moment, index, &name, old_state, new_state.clone());
+            if new_state == ComponentState::Restricted {
+                self.restrict_descendants(index, moment);
+            }
+            if fired {
+                self.preempt(index, moment);
+            }
+        }
+        let node = self.get_mut(index)?;
+        if new_state == ComponentState::Completed {
+            node.completed_before = true;
         }
+        node.state = new_state;
 
         Some(node)
     }
@@ -163,8 +697,29 @@ impl ReBiCycler {
     /// Uses a breadth-first walk of the build order tree to
     /// update the build's state.
     /// Returns a vec of active build components
+    pub fn update(&mut self, judge: &impl EvaluatesConditions) -> Vec<BuildComponent> {
+        let order = self.breadth_first();
+        for index in &order {
+            self.update_component(*index, judge);
+        }
+        // a reaction late in the walk can restrict nodes we've already been through
+        order
+            .into_iter()
+            .filter_map(|index| self.get(index))
+            .filter(|component| component.state == ComponentState::Active)
+            .cloned()
+            .collect()
+    }
+}
+
+impl ReBiCycler {
+    /// Updates the build against the current game state.
+    /// Returns a vec of active build components
     pub fn update_build(&mut self) -> Vec<BuildComponent> {
-        todo!()
+        let mut build = std::mem::take(&mut self.build_order);
+        let active = build.update(self);
+        self.build_order = build;
+        active
     }
 }
 
@@ -176,6 +731,7 @@ mod tests {
         BuildComponent::new(
             "child",
             ConditionGroup::new(&[], ConditionOperator::All),
+            ConditionGroup::new(&[], ConditionOperator::All),
             None,
             true,
         )
@@ -241,6 +797,69 @@ mod tests {
         assert_eq!(tree.to_string(), "ROOT➖\n-child❌\n".to_string());
     }
 
+    #[test]
+    fn update_build_walks_states() {
+        let mut rebi = ReBiCycler::default();
+        let done = BuildComponent::new(
+            "done",
+            ConditionGroup::new(&[], ConditionOperator::All),
+            ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::All),
+            None,
+            true,
+        );
+        let waiting = BuildComponent::new(
+            "waiting",
+            ConditionGroup::new(&[], ConditionOperator::All),
+            ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+            None,
+            true,
+        );
+        assert!(rebi.build_order.add_node(done, Some(0)).is_ok()); // 1
+        assert!(rebi.build_order.add_node(waiting.clone(), Some(1)).is_ok()); // 2
+        assert!(rebi.build_order.add_node(waiting, Some(2)).is_ok()); // 3
+
+        let active = rebi.update_build();
+        assert_eq!(active.len(), 1);
+        assert_eq!(
+            rebi.build_order.to_string(),
+            "ROOT✅\n-done✅\n--waiting⏳\n---waiting➖\n".to_string()
+        );
+
+        rebi.build_order.get_mut(2).unwrap().state = ComponentState::Restricted;
+        assert!(rebi.update_build().is_empty());
+        assert_eq!(
+            rebi.build_order.to_string(),
+            "ROOT✅\n-done✅\n--waiting❌\n---waiting❌\n".to_string()
+        );
+    }
+
+    #[test]
+    fn start_conditions_hold_nodes_back() {
+        let mut rebi = ReBiCycler::default();
+        let held = BuildComponent::new(
+            "held",
+            ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+            ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::All),
+            None,
+            true,
+        );
+        let ready = BuildComponent::new(
+            "ready",
+            ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::All),
+            ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+            None,
+            true,
+        );
+        assert!(rebi.build_order.add_node(held, Some(0)).is_ok());
+        assert!(rebi.build_order.add_node(ready, Some(0)).is_ok());
+
+        assert_eq!(rebi.update_build().len(), 1);
+        assert_eq!(
+            rebi.build_order.to_string(),
+            "ROOT✅\n-ready⏳\n-held➖\n".to_string()
+        );
+    }
+
     #[test]
     fn check_logic() {
         let rebi = ReBiCycler::default();
@@ -284,4 +903,258 @@ mod tests {
             ConditionOperator::ExactlyNOf(2)
         )));
     }
+
+    #[test]
+    fn check_nested_logic() {
+        let rebi = ReBiCycler::default();
+        let all = |c: &[BuildCondition]| ConditionGroup::new(c, ConditionOperator::All);
+
+        // (Always and Never) or Always
+        let either = ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::Any)
+            .with_group(all(&[BuildCondition::Always, BuildCondition::Never]));
+        assert!(rebi.evaluate_condition_group(&either));
+
+        // (Always and Never) or Never
+        let neither = ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::Any)
+            .with_group(all(&[BuildCondition::Always, BuildCondition::Never]));
+        assert!(!rebi.evaluate_condition_group(&neither));
+
+        // not both of those
+        assert!(rebi.evaluate_condition_group(&ConditionGroup::nested(
+            vec![
+                ConditionTerm::Group(either.clone()),
+                ConditionTerm::Group(neither.clone())
+            ],
+            ConditionOperator::NotAll
+        )));
+
+        // exactly one of those, three levels down
+        let exactly_one = ConditionGroup::nested(
+            vec![
+                ConditionTerm::Group(either),
+                ConditionTerm::Group(neither),
+                ConditionTerm::Condition(BuildCondition::Never),
+            ],
+            ConditionOperator::ExactlyNOf(1),
+        );
+        assert!(rebi.evaluate_condition_group(&exactly_one));
+        assert!(!rebi.evaluate_condition_group(
+            &ConditionGroup::new(&[], ConditionOperator::NoneOf).with_group(exactly_one)
+        ));
+    }
+
+    #[test]
+    fn nested_groups_display() {
+        let group = ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::Any)
+            .with_group(ConditionGroup::new(
+                &[BuildCondition::StructureComplete(
+                    rust_sc2::ids::UnitTypeId::Gateway,
+                )],
+                ConditionOperator::ExactlyNOf(1),
+            ));
+        assert_eq!(
+            group.to_string(),
+            "Any(Always, ExactlyNOf(1, StructureComplete(Gateway)))"
+        );
+    }
+
+    /// only knows how many observers we have
+    struct ObserverCount(usize);
+
+    impl EvaluatesConditions for ObserverCount {
+        fn evaluate_condition(&self, condition: &BuildCondition) -> bool {
+            match condition {
+                BuildCondition::TotalAndOrderedAtLeast(_, count) => self.0 >= *count,
+                BuildCondition::Always => true,
+                _ => false,
+            }
+        }
+
+        fn moment(&self) -> Moment {
+            Moment::default()
+        }
+    }
+
+    #[test]
+    fn quotas_top_up_after_losses() {
+        let mut tree = BuildOrderTree::default();
+        let observers = BuildComponent::quota(
+            "keep 2 observers",
+            ConditionGroup::new(&[], ConditionOperator::All),
+            UnitTypeId::Observer,
+            2,
+            BuildOrderAction::Train(
+                UnitTypeId::Observer,
+                rust_sc2::ids::AbilityId::RoboticsFacilityTrainObserver,
+            ),
+        );
+        let after = BuildComponent::new(
+            "after",
+            ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::All),
+            ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+            None,
+            true,
+        );
+        assert!(tree.add_node(observers, Some(0)).is_ok()); // 1
+        assert!(tree.add_node(after.clone(), Some(1)).is_ok()); // 2
+        assert!(tree.add_node(after, Some(1)).is_ok()); // 3
+
+        assert_eq!(tree.update(&ObserverCount(1)).len(), 1);
+        assert_eq!(
+            tree.to_string(),
+            "ROOT✅\n-keep 2 observers🔁⏳\n--after➖\n--after➖\n"
+        );
+
+        assert_eq!(tree.update(&ObserverCount(2)).len(), 2);
+        assert_eq!(
+            tree.to_string(),
+            "ROOT✅\n-keep 2 observers🔁✅\n--after⏳\n--after⏳\n"
+        );
+
+        // lost one, so the quota wakes back up and the children keep going
+        assert_eq!(tree.update(&ObserverCount(1)).len(), 3);
+        assert_eq!(
+            tree.to_string(),
+            "ROOT✅\n-keep 2 observers🔁⏳\n--after⏳\n--after⏳\n"
+        );
+        assert_eq!(tree.update(&ObserverCount(2)).len(), 2);
+        assert_eq!(tree.timeline.transitions().len(), 7);
+    }
+
+    /// sees a spawning pool when told to
+    struct PoolScout(bool);
+
+    impl EvaluatesConditions for PoolScout {
+        fn evaluate_condition(&self, condition: &BuildCondition) -> bool {
+            match condition {
+                BuildCondition::EnemyUnitSeen(_) => self.0,
+                BuildCondition::Always => true,
+                _ => false,
+            }
+        }
+
+        fn moment(&self) -> Moment {
+            Moment::default()
+        }
+    }
+
+    #[test]
+    fn reactions_preempt_their_siblings() {
+        let build = TreePointer::new()
+            .root(
+                "opener",
+                ConditionGroup::new(&[], ConditionOperator::All),
+                ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::All),
+                None,
+                true,
+            )
+            .and_then(|t| {
+                t.child(
+                    "greedy",
+                    ConditionGroup::new(&[], ConditionOperator::All),
+                    ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+                    Some(BuildOrderAction::Expand),
+                    true,
+                )
+            })
+            .and_then(|t| {
+                t.root(
+                    "early pool",
+                    ConditionGroup::new(
+                        &[BuildCondition::EnemyUnitSeen(UnitTypeId::SpawningPool)],
+                        ConditionOperator::All,
+                    ),
+                    ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::All),
+                    None,
+                    true,
+                )
+            })
+            .and_then(TreePointer::reaction)
+            .and_then(|t| {
+                t.child(
+                    "panic gateway",
+                    ConditionGroup::new(&[], ConditionOperator::All),
+                    ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+                    Some(BuildOrderAction::Construct(UnitTypeId::Gateway)),
+                    true,
+                )
+            });
+        assert!(build.is_ok());
+        let mut tree = build.unwrap().tree;
+
+        let active = tree.update(&PoolScout(false));
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].priority(), Priority::Normal);
+
+        let active = tree.update(&PoolScout(true));
+        assert_eq!(
+            tree.to_string(),
+            "ROOT✅\n-early pool✅\n--panic gateway⏳\n-opener✅\n--greedy❌\n"
+        );
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].name(), "panic gateway");
+        assert_eq!(active[0].priority(), Priority::Urgent);
+    }
+
+    #[test]
+    fn reactions_leave_other_branches_alone() {
+        let build = TreePointer::new()
+            .empty_root(Some("opener"))
+            .and_then(|t| {
+                t.leaf(
+                    "greedy",
+                    ConditionGroup::new(&[], ConditionOperator::All),
+                    ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+                    Some(BuildOrderAction::Expand),
+                    true,
+                )
+            })
+            .and_then(|t| {
+                t.child(
+                    "early pool",
+                    ConditionGroup::new(
+                        &[BuildCondition::EnemyUnitSeen(UnitTypeId::SpawningPool)],
+                        ConditionOperator::All,
+                    ),
+                    ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::All),
+                    None,
+                    true,
+                )
+            })
+            .and_then(TreePointer::reaction)
+            .and_then(|t| {
+                t.child(
+                    "panic gateway",
+                    ConditionGroup::new(&[], ConditionOperator::All),
+                    ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+                    Some(BuildOrderAction::Construct(UnitTypeId::Gateway)),
+                    true,
+                )
+            })
+            .and_then(|t| {
+                t.root(
+                    "tech",
+                    ConditionGroup::new(&[], ConditionOperator::All),
+                    ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+                    Some(BuildOrderAction::Construct(UnitTypeId::CyberneticsCore)),
+                    true,
+                )
+            });
+        assert!(build.is_ok());
+        let mut tree = build.unwrap().tree;
+
+        assert_eq!(tree.update(&PoolScout(false)).len(), 2);
+
+        // only the opener's branches give way, the tech keeps going
+        let active = tree.update(&PoolScout(true));
+        assert_eq!(
+            tree.to_string(),
+            "ROOT✅\n-tech⏳\n-opener✅\n--early pool✅\n---panic gateway⏳\n--greedy❌\n"
+        );
+        let names: Vec<&str> = active.iter().map(BuildComponent::name).collect();
+        assert_eq!(names, ["tech", "panic gateway"]);
+
+        // and it stays that way
+        assert_eq!(tree.update(&PoolScout(true)).len(), 2);
+    }
 }
@@ -0,0 +1,481 @@
+use std::{collections::HashSet, fmt::Display};
+
+use rust_sc2::ids::UnitTypeId;
+
+use crate::{
+    build_orders::{BuildCondition, BuildOrderAction},
+    build_tree::{
+        BuildComponent, BuildOrderTree, ConditionGroup, ConditionOperator, ConditionTerm,
+    },
+    tech_tree::researched_at,
+};
+
+/// Something wrong with a node that we can spot before the game starts.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub node: usize,
+    pub name: String,
+    pub problem: ValidationProblem,
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Build node {} '{}': {}",
+            self.node, self.name, self.problem
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidationProblem {
+    /// A `Research` whose researcher isn't guaranteed to exist when the node activates
+    MissingResearcher(UnitTypeId),
+    /// A `Construct` whose tech requirement isn't guaranteed to exist when the node activates
+    MissingPrerequisite {
+        structure: UnitTypeId,
+        needs: UnitTypeId,
+    },
+    /// `SupplyBetween(low, high)` with nothing in between
+    EmptySupplyRange(u32, u32),
+    /// `ExactlyNOf(n)` over fewer than n conditions
+    NotEnoughConditions { needed: usize, have: usize },
+    /// The start conditions can never all be true
+    NeverStarts,
+    /// An ancestor never starts or never completes, so this node never activates
+    BlockedBy(usize),
+}
+
+impl Display for ValidationProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingResearcher(researcher) => {
+                write!(
+                    f,
+                    "research needs a {researcher:?} that isn't built before it"
+                )
+            }
+            Self::MissingPrerequisite { structure, needs } => {
+                write!(
+                    f,
+                    "{structure:?} needs a {needs:?} that isn't built before it"
+                )
+            }
+            Self::EmptySupplyRange(low, high) => {
+                write!(f, "SupplyBetween({low}, {high}) can never be true")
+            }
+            Self::NotEnoughConditions { needed, have } => {
+                write!(f, "ExactlyNOf({needed}) over only {have} conditions")
+            }
+            Self::NeverStarts => write!(f, "start conditions can never be met"),
+            Self::BlockedBy(ancestor) => {
+                write!(f, "blocked by node {ancestor}, which never finishes")
+            }
+        }
+    }
+}
+
+impl BuildOrderTree {
+    /// Looks for builds that can't work: tech that's never built, conditions
+    /// that contradict themselves and nodes that will never activate.
+    /// Meant to run before the game starts, so mistakes don't show up as a stuck bot.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        for index in self.breadth_first() {
+            let Some(component) = self.get(index) else {
+                continue;
+            };
+            let ancestors = self.ancestors(index);
+            let mut problems = Vec::new();
+
+            for group in [component.start_when(), component.complete_when()] {
+                problems.extend(contradictions(group));
+            }
+
+            let available = self.guaranteed_structures(component, &ancestors);
+            match component.action() {
+                Some(BuildOrderAction::Research(_, _, researcher))
+                    if !available.contains(&researcher) =>
+                {
+                    problems.push(ValidationProblem::MissingResearcher(researcher));
+                }
+                Some(BuildOrderAction::UpgradeTo(line, _)) => {
+                    if let Some((_, researcher)) = researched_at(line.levels()[0]) {
+                        if !available.contains(&researcher) {
+                            problems.push(ValidationProblem::MissingResearcher(researcher));
+                        }
+                    }
+                }
+                Some(BuildOrderAction::Construct(structure)) => {
+                    if let Some(needs) = crate::structure_prerequisite(structure) {
+                        if !available.contains(&needs) {
+                            problems
+                                .push(ValidationProblem::MissingPrerequisite { structure, needs });
+                        }
+                    }
+                }
+                _ => (),
+            }
+
+            if !can_be_true(component.start_when()) {
+                problems.push(ValidationProblem::NeverStarts);
+            } else if let Some(blocker) = ancestors.iter().copied().find(|a| {
+                self.get(*a).is_some_and(|c| {
+                    !can_be_true(c.start_when()) || !can_be_true(c.complete_when())
+                })
+            }) {
+                problems.push(ValidationProblem::BlockedBy(blocker));
+            }
+
+            issues.extend(problems.into_iter().map(|problem| ValidationIssue {
+                node: index,
+                name: component.name().to_string(),
+                problem,
+            }));
+        }
+        issues
+    }
+
+    /// parents first, up to the root
+    fn ancestors(&self, node: usize) -> Vec<usize> {
+        let mut ancestors = Vec::new();
+        let mut current = node;
+        while let Some(parent) = self.parent(current) {
+            ancestors.push(parent);
+            current = parent;
+        }
+        ancestors
+    }
+
+    /// Structures we know exist by the time this node activates:
+    /// whatever the ancestors constructed or waited on, whatever this node waits on,
+    /// and the Nexus we start with.
+    fn guaranteed_structures(
+        &self,
+        component: &BuildComponent,
+        ancestors: &[usize],
+    ) -> HashSet<UnitTypeId> {
+        let mut available = HashSet::from([UnitTypeId::Nexus]);
+        available.extend(required_structures(component.start_when()));
+
+        for ancestor in ancestors.iter().filter_map(|a| self.get(*a)) {
+            if let Some(BuildOrderAction::Construct(structure)) = ancestor.action() {
+                available.insert(structure);
+            }
+            available.extend(required_structures(ancestor.start_when()));
+            available.extend(required_structures(ancestor.complete_when()));
+        }
+
+        if available.contains(&UnitTypeId::WarpGate) {
+            available.insert(UnitTypeId::Gateway);
+        }
+        available
+    }
+}
+
+/// structures that must exist for this group to be true
+fn required_structures(group: &ConditionGroup) -> HashSet<UnitTypeId> {
+    let per_term = group.terms.iter().map(|term| match term {
+        ConditionTerm::Condition(condition) => required_structure(condition).into_iter().collect(),
+        ConditionTerm::Group(group) => required_structures(group),
+    });
+    match group.operator {
+        ConditionOperator::All => per_term.flatten().collect(),
+        ConditionOperator::Any => per_term
+            .reduce(|so_far, needed| so_far.intersection(&needed).copied().collect())
+            .unwrap_or_default(),
+        _ => HashSet::new(),
+    }
+}
+
+fn required_structure(condition: &BuildCondition) -> Option<UnitTypeId> {
+    let structure = match condition {
+        BuildCondition::StructureComplete(structure) => *structure,
+        BuildCondition::AtLeastCount(structure, count)
+        | BuildCondition::TotalAndOrderedAtLeast(structure, count)
+            if *count > 0 =>
+        {
+            *structure
+        }
+        _ => return None,
+    };
+    if structure == UnitTypeId::WarpGate {
+        Some(UnitTypeId::Gateway)
+    } else {
+        Some(structure)
+    }
+}
+
+fn contradictions(group: &ConditionGroup) -> Vec<ValidationProblem> {
+    let mut problems = Vec::new();
+    for term in &group.terms {
+        match term {
+            ConditionTerm::Condition(BuildCondition::SupplyBetween(low, high)) if low >= high => {
+                problems.push(ValidationProblem::EmptySupplyRange(*low, *high));
+            }
+            ConditionTerm::Condition(_) => (),
+            ConditionTerm::Group(group) => problems.extend(contradictions(group)),
+        }
+    }
+
+    if let ConditionOperator::ExactlyNOf(needed) = group.operator {
+        if group.terms.len() < needed {
+            problems.push(ValidationProblem::NotEnoughConditions {
+                needed,
+                have: group.terms.len(),
+            });
+        }
+    }
+    problems
+}
+
+/// conditions that can't ever hold, whatever the game state
+const fn is_impossible(condition: &BuildCondition) -> bool {
+    match condition {
+        BuildCondition::Never
+        | BuildCondition::LessThanCount(_, 0)
+        | BuildCondition::MineralsBelow(0)
+        | BuildCondition::GasBelow(0) => true,
+        BuildCondition::SupplyBetween(low, high) => *low >= *high,
+        _ => false,
+    }
+}
+
+fn term_can_be_true(term: &ConditionTerm) -> bool {
+    match term {
+        ConditionTerm::Condition(condition) => !is_impossible(condition),
+        ConditionTerm::Group(group) => can_be_true(group),
+    }
+}
+
+fn term_must_be_true(term: &ConditionTerm) -> bool {
+    match term {
+        ConditionTerm::Condition(condition) => matches!(condition, BuildCondition::Always),
+        ConditionTerm::Group(group) => must_be_true(group),
+    }
+}
+
+/// false only if the group is false in every game state we can think of
+fn can_be_true(group: &ConditionGroup) -> bool {
+    let terms = &group.terms;
+    match group.operator {
+        ConditionOperator::All => terms.iter().all(term_can_be_true),
+        ConditionOperator::Any => terms.iter().any(term_can_be_true),
+        ConditionOperator::NoneOf => !terms.iter().any(term_must_be_true),
+        ConditionOperator::NotAll => !terms.iter().all(term_must_be_true),
+        ConditionOperator::ExactlyNOf(needed) => {
+            let possible = terms.iter().filter(|t| term_can_be_true(t)).count();
+            let forced = terms.iter().filter(|t| term_must_be_true(t)).count();
+            forced <= needed && needed <= possible
+        }
+    }
+}
+
+/// true only if the group is true in every game state we can think of
+fn must_be_true(group: &ConditionGroup) -> bool {
+    let terms = &group.terms;
+    match group.operator {
+        ConditionOperator::All => terms.iter().all(term_must_be_true),
+        ConditionOperator::Any => terms.iter().any(term_must_be_true),
+        ConditionOperator::NoneOf => !terms.iter().any(term_can_be_true),
+        ConditionOperator::NotAll => !terms.iter().all(term_can_be_true),
+        ConditionOperator::ExactlyNOf(needed) => {
+            let possible = terms.iter().filter(|t| term_can_be_true(t)).count();
+            let forced = terms.iter().filter(|t| term_must_be_true(t)).count();
+            forced == needed && possible == needed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_sc2::ids::{AbilityId, UpgradeId};
+
+    use super::*;
+    use crate::build_tree::TreePointer;
+
+    fn empty() -> ConditionGroup {
+        ConditionGroup::new(&[], ConditionOperator::All)
+    }
+
+    fn problems(tree: &BuildOrderTree) -> Vec<ValidationProblem> {
+        tree.validate().into_iter().map(|i| i.problem).collect()
+    }
+
+    #[test]
+    fn standard_build_is_valid() {
+        let tree = BuildOrderTree::nexus_first_two_base_charge().unwrap();
+        assert_eq!(tree.validate(), vec![]);
+    }
+
+    #[test]
+    fn checked_in_builds_are_valid() {
+        let names = BuildOrderTree::available();
+        assert!(names.len() > 1);
+        for name in names {
+            let tree = BuildOrderTree::from_name(&name);
+            assert!(tree.is_ok(), "{name}: {:?}", tree.err());
+            assert_eq!(tree.unwrap().validate(), vec![], "{name}");
+        }
+    }
+
+    #[test]
+    fn tech_out_of_order() {
+        let tree = TreePointer::new()
+            .root(
+                "twilight",
+                empty(),
+                empty(),
+                Some(BuildOrderAction::Construct(UnitTypeId::TwilightCouncil)),
+                true,
+            )
+            .and_then(|t| {
+                t.child(
+                    "charge",
+                    empty(),
+                    empty(),
+                    Some(BuildOrderAction::Research(
+                        UpgradeId::Charge,
+                        AbilityId::ResearchCharge,
+                        UnitTypeId::TwilightCouncil,
+                    )),
+                    true,
+                )
+            })
+            .and_then(|t| {
+                t.root(
+                    "blink",
+                    empty(),
+                    empty(),
+                    Some(BuildOrderAction::Research(
+                        UpgradeId::BlinkTech,
+                        AbilityId::ResearchBlink,
+                        UnitTypeId::TwilightCouncil,
+                    )),
+                    true,
+                )
+            })
+            .unwrap()
+            .tree;
+
+        assert_eq!(
+            problems(&tree),
+            vec![
+                ValidationProblem::MissingPrerequisite {
+                    structure: UnitTypeId::TwilightCouncil,
+                    needs: UnitTypeId::CyberneticsCore
+                },
+                ValidationProblem::MissingResearcher(UnitTypeId::TwilightCouncil),
+            ]
+        );
+    }
+
+    #[test]
+    fn contradictory_groups() {
+        let tree = TreePointer::new()
+            .root(
+                "backwards supply",
+                empty(),
+                ConditionGroup::new(
+                    &[BuildCondition::SupplyBetween(20, 10)],
+                    ConditionOperator::Any,
+                ),
+                None,
+                true,
+            )
+            .and_then(|t| {
+                t.root(
+                    "three of two",
+                    ConditionGroup::new(
+                        &[BuildCondition::Always, BuildCondition::Always],
+                        ConditionOperator::ExactlyNOf(3),
+                    ),
+                    empty(),
+                    None,
+                    true,
+                )
+            })
+            .unwrap()
+            .tree;
+
+        assert_eq!(
+            problems(&tree),
+            vec![
+                ValidationProblem::EmptySupplyRange(20, 10),
+                ValidationProblem::NotEnoughConditions { needed: 3, have: 2 },
+                ValidationProblem::NeverStarts,
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_groups_are_checked() {
+        let never = ConditionGroup::new(
+            &[BuildCondition::Never, BuildCondition::SupplyBetween(5, 5)],
+            ConditionOperator::Any,
+        );
+        let tree = TreePointer::new()
+            .root(
+                "buried never",
+                ConditionGroup::new(&[BuildCondition::Always], ConditionOperator::All)
+                    .with_group(never.clone()),
+                empty(),
+                None,
+                true,
+            )
+            .and_then(|t| {
+                t.root(
+                    "not never",
+                    ConditionGroup::new(&[], ConditionOperator::NoneOf).with_group(never),
+                    empty(),
+                    None,
+                    true,
+                )
+            })
+            .unwrap()
+            .tree;
+
+        assert_eq!(
+            problems(&tree),
+            vec![
+                ValidationProblem::EmptySupplyRange(5, 5),
+                ValidationProblem::NeverStarts,
+                ValidationProblem::EmptySupplyRange(5, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn unreachable_nodes() {
+        let tree = TreePointer::new()
+            .root(
+                "forever",
+                empty(),
+                ConditionGroup::new(&[BuildCondition::Never], ConditionOperator::All),
+                None,
+                true,
+            )
+            .and_then(|t| t.child("after forever", empty(), empty(), None, true))
+            .and_then(|t| t.child("way after forever", empty(), empty(), None, true))
+            .unwrap()
+            .tree;
+
+        assert_eq!(
+            tree.validate(),
+            vec![
+                ValidationIssue {
+                    node: 2,
+                    name: "after forever".to_string(),
+                    problem: ValidationProblem::BlockedBy(1)
+                },
+                ValidationIssue {
+                    node: 3,
+                    name: "way after forever".to_string(),
+                    problem: ValidationProblem::BlockedBy(1)
+                },
+            ]
+        );
+    }
+}
@@ -1,17 +1,27 @@
 use std::{collections::HashMap, fmt::Display};
 
-use rust_sc2::{action::Target, ids::UnitTypeId, prelude::Point2, unit::Unit};
+use rust_sc2::{
+    action::Target,
+    ids::{BuffId, UnitTypeId},
+    prelude::Point2,
+    unit::Unit,
+};
 
 use crate::{
     army::MissionType,
     errors::{AssignmentError, AssignmentIssue, BuildError},
     protoss_bot::ReBiCycler,
     siting::{ConstructionSite, LocationType},
+    supply::{
+        Producer, SupplyForecast, CHRONO_SECONDS, NEXUS_SUPPLY, PYLON_BUILD_SECONDS, PYLON_SUPPLY,
+    },
+    warp_in::warp_cooldown,
     Assigns, Tag,
 };
 
 const PROJECT_MAX_LIFETIME: u32 = 23 * 120;
 const CONSTRUCTION_RALLY_DISTANCE: f32 = 7.0;
+const FRAMES_PER_SECOND: f32 = 22.4;
 
 #[derive(Default)]
 pub struct ConstructionManager {
@@ -58,6 +68,13 @@ impl ConstructionManager {
         self.active_projects.get(&location)
     }
 
+    /// whether this worker is on its way to put something down for us
+    pub fn is_builder(&self, tag: u64) -> bool {
+        self.active_projects
+            .values()
+            .any(|project| project.builder == Some(tag))
+    }
+
     fn log_construction_queued(
         &mut self,
         location: Point2,
@@ -214,44 +231,95 @@ impl ReBiCycler {
             .collect();
     }
 
+    /// Starts enough pylons that nothing we're making runs out of supply by the time they're up
     pub fn maintain_supply(&mut self) -> Result<(), BuildError> {
-        if self.supply_cap == 200 {
-            return Ok(());
+        for _ in 0..self.supply_forecast().pylons_needed() {
+            self.build(UnitTypeId::Pylon)?;
         }
-        let production_structures = self
-            .units
-            .my
-            .structures
-            .iter()
-            .filter(|u| crate::is_protoss_production(&u.type_id()))
-            .count();
-
-        let over_supply = self.supply_used.saturating_sub(self.supply_cap);
-
-        let producing_workers = self.counter().ordered().count(UnitTypeId::Probe) > 0;
+        Ok(())
+    }
 
-        let wanted_free_supply = production_structures * 2 + if producing_workers { 2 } else { 0 };
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn supply_forecast(&self) -> SupplyForecast {
+        let seconds = |unit_type| self.get_unit_cost(unit_type).time / FRAMES_PER_SECOND;
+        // we can't see how long a chrono has left, so assume all of it, which errs towards early pylons
+        let chrono = |structure: &Unit| {
+            if structure.has_buff(BuffId::ChronoBoostEnergyCost) {
+                CHRONO_SECONDS
+            } else {
+                0.0
+            }
+        };
 
-        if self.supply_left >= wanted_free_supply as u32 {
-            return Ok(());
+        let mut producers = Vec::new();
+        for structure in self.units.my.structures.ready().iter() {
+            if structure.type_id() == UnitTypeId::WarpGate {
+                let cooldown = self.warp_gates.cooldown_left(structure.tag(), self.time);
+                let cycle = warp_cooldown(UnitTypeId::Zealot);
+                producers.push(Producer::new(2, cooldown, cycle, chrono(structure)));
+                continue;
+            }
+            match structure.order() {
+                Some((ability, _, progress)) => {
+                    let unit_type = crate::ability_produces(ability);
+                    if unit_type == UnitTypeId::NotAUnit {
+                        continue;
+                    }
+                    let build_time = seconds(unit_type);
+                    producers.push(Producer::new(
+                        self.get_unit_cost(unit_type).supply as u32,
+                        build_time * (1.0 - progress),
+                        build_time,
+                        chrono(structure),
+                    ));
+                }
+                None if crate::is_protoss_production(&structure.type_id()) => {
+                    let supply = if structure.type_id() == UnitTypeId::Nexus {
+                        1
+                    } else {
+                        2
+                    };
+                    producers.push(Producer::idle(supply));
+                }
+                None => (),
+            }
         }
 
-        let ordered_pylons = self.counter().ordered().count(UnitTypeId::Pylon);
-        let almost_done_nexi = self
+        let mut incoming: Vec<(f32, u32)> = self
             .units
             .my
-            .townhalls
+            .structures
+            .not_ready()
             .iter()
-            .filter(|u| !u.is_ready() && u.is_almost_ready())
-            .count();
-
-        let pending_new_supply = 8 * ordered_pylons + 15 * almost_done_nexi;
-
-        if pending_new_supply >= (wanted_free_supply + over_supply as usize) {
-            return Ok(());
+            .filter_map(|s| {
+                let supply = match s.type_id() {
+                    UnitTypeId::Pylon => PYLON_SUPPLY,
+                    UnitTypeId::Nexus => NEXUS_SUPPLY,
+                    _ => return None,
+                };
+                Some((seconds(s.type_id()) * (1.0 - s.build_progress()), supply))
+            })
+            .collect();
+        // pylons a probe is still on its way to place
+        for project in self.construction_manager.active_projects.values() {
+            let placed = !self
+                .units
+                .my
+                .structures
+                .closer(1.0, project.site.location())
+                .is_empty();
+            if project.building == UnitTypeId::Pylon && !placed {
+                incoming.push((PYLON_BUILD_SECONDS, PYLON_SUPPLY));
+            }
         }
 
-        self.build(UnitTypeId::Pylon)
+        SupplyForecast {
+            used: self.supply_used,
+            cap: self.supply_cap,
+            producers,
+            incoming,
+        }
     }
 
     pub fn process_construction_projects(&mut self) {
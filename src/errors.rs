@@ -36,11 +36,11 @@ pub enum BuildError {
     NoTrainer,
     NoResearcher(UpgradeId),
     AllBusy(AbilityId),
-    AllChronoed(AbilityId),
     AlreadyResearching,
     NoBuildItemsLeft,
     WarpGateNotResearched,
     NoPower(Point2),
+    WouldBlockPath(Point2, UnitTypeId),
 }
 #[derive(Debug)]
 pub enum BuildingTransitionError {
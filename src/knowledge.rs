@@ -14,7 +14,6 @@ use rust_sc2::{
 pub struct Knowledge {
     pub confirmed_dead: HashMap<u64, UnitKnowledge>,
     pub first_seen_unit_times: HashMap<UnitTypeId, usize>,
-    pub first_seen_friendly_times: HashMap<UnitTypeId, usize>,
     pub seen_units: HashMap<u64, UnitKnowledge>,
     pub confirmed_enemy_race: Option<Race>,
     pub total_spend: (u32, u32),
@@ -91,9 +90,11 @@ impl Knowledge {
     }
 
     /// We want to know when we first saw new enemy units. I think this will help us determine when we're being rushed, or benchmark our own build
+    /// Our own units are skipped so they don't look like enemies.
     pub fn add_newly_seen_units(&mut self, units: &Units, frame_no: usize) {
         let new_units: HashSet<UnitTypeId> = units
             .iter()
+            .filter(|u| u.is_enemy())
             .filter_map(|u| {
                 if self.first_seen_unit_times.contains_key(&u.type_id()) {
                     None
@@ -134,9 +135,86 @@ impl Knowledge {
             .filter(|uk| uk.is_structure && matches!(uk.alliance, Alliance::Enemy))
             .collect()
     }
+
+    /// enemy structures of this type that we've seen and haven't seen die
+    pub fn count_enemy_structures(&self, structure_type: UnitTypeId) -> usize {
+        self.get_enemy_buildings()
+            .iter()
+            .filter(|uk| uk.type_id == structure_type)
+            .count()
+    }
+
+    /// enemy townhalls we know of that aren't in their main
+    pub fn count_enemy_expansions(&self, enemy_start: Point2) -> usize {
+        self.get_enemy_buildings()
+            .iter()
+            .filter(|uk| is_townhall(uk.type_id))
+            .filter(|uk| {
+                crate::distance_squared(&uk.last_position, &enemy_start)
+                    > MAIN_BASE_RADIUS * MAIN_BASE_RADIUS
+            })
+            .count()
+    }
+}
+
+/// a townhall closer than this to the enemy start location is their main
+const MAIN_BASE_RADIUS: f32 = 10.0;
+
+const fn is_townhall(unit: UnitTypeId) -> bool {
+    matches!(
+        unit,
+        UnitTypeId::Nexus
+            | UnitTypeId::CommandCenter
+            | UnitTypeId::CommandCenterFlying
+            | UnitTypeId::OrbitalCommand
+            | UnitTypeId::OrbitalCommandFlying
+            | UnitTypeId::PlanetaryFortress
+            | UnitTypeId::Hatchery
+            | UnitTypeId::Lair
+            | UnitTypeId::Hive
+    )
 }
 
 #[derive(Debug)]
 pub enum KnowledgeError {
     UnknownUnitDestroyed,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enemy_structure(tag: u64, type_id: UnitTypeId, x: f32) -> (u64, UnitKnowledge) {
+        (
+            tag,
+            UnitKnowledge {
+                type_id,
+                last_seen: 0,
+                last_position: Point2::new(x, 0.0),
+                alliance: Alliance::Enemy,
+                is_structure: true,
+            },
+        )
+    }
+
+    #[test]
+    fn counts_enemy_structures_and_expansions() {
+        let mut knowledge = Knowledge {
+            seen_units: HashMap::from([
+                enemy_structure(1, UnitTypeId::Hatchery, 0.0),
+                enemy_structure(2, UnitTypeId::Hatchery, 30.0),
+                enemy_structure(3, UnitTypeId::Hatchery, 60.0),
+                enemy_structure(4, UnitTypeId::SpawningPool, 5.0),
+            ]),
+            ..Default::default()
+        };
+        let enemy_start = Point2::new(0.0, 0.0);
+
+        assert_eq!(knowledge.count_enemy_structures(UnitTypeId::Hatchery), 3);
+        assert_eq!(knowledge.count_enemy_expansions(enemy_start), 2);
+
+        assert!(knowledge.unit_destroyed(3).is_ok());
+        assert_eq!(knowledge.count_enemy_structures(UnitTypeId::Hatchery), 2);
+        assert_eq!(knowledge.count_enemy_expansions(enemy_start), 1);
+    }
+}
@@ -8,23 +8,39 @@ use std::{
 mod army;
 mod assignment_manager;
 mod base_manager;
+mod budget;
+mod build_file;
 mod build_order_definitions;
 mod build_order_manager;
 mod build_orders;
+mod build_simulator;
+mod build_timeline;
 mod build_tree;
+mod build_validation;
 mod chatter;
 mod construction;
 mod errors;
 mod knowledge;
+mod map_analysis;
 mod map_viz;
 mod micro;
 mod mining;
 mod monitor;
+mod nexus_energy;
+mod placement_grid;
+mod production;
 pub mod protoss_bot;
+mod pylon_layout;
 mod readout;
 mod scouting;
 mod siting;
+mod strategy;
 mod strong_types;
+mod supply;
+mod tech_tree;
+mod upgrades;
+mod walls;
+mod warp_in;
 
 pub const PYLON_POWER_RADIUS: f32 = 6.5;
 pub const PRISM_POWER_RADIUS: f32 = 3.75;
@@ -147,7 +163,7 @@ pub const fn is_protoss_production(unit: &UnitTypeId) -> bool {
             | UnitTypeId::Gateway
             | UnitTypeId::WarpGate
             | UnitTypeId::Stargate
-            | UnitTypeId::RoboticsBay
+            | UnitTypeId::RoboticsFacility
     )
 }
 
@@ -165,6 +181,23 @@ pub const fn is_protoss_tech(unit: UnitTypeId) -> bool {
     )
 }
 
+/// The structure that has to exist before we can start this one.
+#[must_use]
+pub const fn structure_prerequisite(structure: UnitTypeId) -> Option<UnitTypeId> {
+    match structure {
+        UnitTypeId::CyberneticsCore => Some(UnitTypeId::Gateway),
+        UnitTypeId::PhotonCannon => Some(UnitTypeId::Forge),
+        UnitTypeId::ShieldBattery
+        | UnitTypeId::TwilightCouncil
+        | UnitTypeId::Stargate
+        | UnitTypeId::RoboticsFacility => Some(UnitTypeId::CyberneticsCore),
+        UnitTypeId::TemplarArchive | UnitTypeId::DarkShrine => Some(UnitTypeId::TwilightCouncil),
+        UnitTypeId::RoboticsBay => Some(UnitTypeId::RoboticsFacility),
+        UnitTypeId::FleetBeacon => Some(UnitTypeId::Stargate),
+        _ => None,
+    }
+}
+
 #[must_use]
 pub const fn structure_needs_power(unit: &UnitTypeId) -> bool {
     if is_protoss_building(unit) {
@@ -227,6 +260,28 @@ const fn ability_produces(ability: AbilityId) -> UnitTypeId {
     }
 }
 
+/// The structure a probe is on its way to build, if that's what the order is
+const fn ability_builds(ability: AbilityId) -> Option<UnitTypeId> {
+    match ability {
+        AbilityId::ProtossBuildNexus => Some(UnitTypeId::Nexus),
+        AbilityId::ProtossBuildPylon => Some(UnitTypeId::Pylon),
+        AbilityId::ProtossBuildAssimilator => Some(UnitTypeId::Assimilator),
+        AbilityId::ProtossBuildGateway => Some(UnitTypeId::Gateway),
+        AbilityId::ProtossBuildForge => Some(UnitTypeId::Forge),
+        AbilityId::ProtossBuildCyberneticsCore => Some(UnitTypeId::CyberneticsCore),
+        AbilityId::ProtossBuildPhotonCannon => Some(UnitTypeId::PhotonCannon),
+        AbilityId::ProtossBuildShieldBattery => Some(UnitTypeId::ShieldBattery),
+        AbilityId::ProtossBuildTwilightCouncil => Some(UnitTypeId::TwilightCouncil),
+        AbilityId::ProtossBuildRoboticsFacility => Some(UnitTypeId::RoboticsFacility),
+        AbilityId::ProtossBuildRoboticsBay => Some(UnitTypeId::RoboticsBay),
+        AbilityId::ProtossBuildStargate => Some(UnitTypeId::Stargate),
+        AbilityId::ProtossBuildFleetBeacon => Some(UnitTypeId::FleetBeacon),
+        AbilityId::ProtossBuildTemplarArchive => Some(UnitTypeId::TemplarArchive),
+        AbilityId::ProtossBuildDarkShrine => Some(UnitTypeId::DarkShrine),
+        _ => None,
+    }
+}
+
 #[must_use]
 pub fn count_unit_types(units: &Units) -> HashMap<UnitTypeId, usize> {
     let mut counts: HashMap<UnitTypeId, usize> = HashMap::new();
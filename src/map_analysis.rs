@@ -0,0 +1,478 @@
+use std::collections::{HashSet, VecDeque};
+
+use rust_sc2::prelude::*;
+
+use crate::{
+    placement_grid::{PlacementGrid, Tile},
+    protoss_bot::ReBiCycler,
+};
+
+/// a ramp has to climb at least this much terrain height from bottom to top,
+/// so flat unbuildable ground doesn't count
+const MIN_RAMP_RISE: u8 = 8;
+/// anything smaller is a doodad, not a ramp
+const MIN_RAMP_CELLS: usize = 4;
+/// gaps narrower than this are between mineral patches and the like
+const MIN_CHOKE_WIDTH: f32 = 3.0;
+/// wider than this and it's open ground
+const MAX_CHOKE_WIDTH: f32 = 16.0;
+/// how far either side of a choke we look to see it open up
+const CHOKE_DEPTH: isize = 4;
+/// how much wider it has to get there to be a choke rather than a corridor
+const CHOKE_OPENING: f32 = 2.0;
+/// a choke this close to the main ramp is the ramp again
+const RAMP_CHOKE_SEPARATION: f32 = 6.0;
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+/// the lines we measure a gap along, the other halves are just these backwards
+const CROSSINGS: [(isize, isize); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+
+/// The grids the analysis reads, row by row from the bottom
+#[derive(Debug, Clone, Default)]
+pub struct TerrainGrids {
+    width: usize,
+    height: usize,
+    pathable: Vec<bool>,
+    placeable: Vec<bool>,
+    elevation: Vec<u8>,
+}
+
+impl TerrainGrids {
+    pub fn new(
+        width: usize,
+        height: usize,
+        pathable: Vec<bool>,
+        placeable: Vec<bool>,
+        elevation: Vec<u8>,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            pathable,
+            placeable,
+            elevation,
+        }
+    }
+
+    fn index(&self, (x, y): Tile) -> Option<usize> {
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+
+    fn step(&self, (x, y): Tile, (dx, dy): (isize, isize)) -> Option<Tile> {
+        let tile = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+        self.index(tile).map(|_| tile)
+    }
+
+    fn is_pathable(&self, tile: Tile) -> bool {
+        self.index(tile).is_some_and(|i| self.pathable[i])
+    }
+
+    fn is_placeable(&self, tile: Tile) -> bool {
+        self.index(tile).is_some_and(|i| self.placeable[i])
+    }
+
+    fn elevation(&self, tile: Tile) -> u8 {
+        self.index(tile).map_or(0, |i| self.elevation[i])
+    }
+
+    fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| (x, y)))
+    }
+
+    fn neighbours(&self, tile: Tile) -> impl Iterator<Item = Tile> + '_ {
+        NEIGHBOURS
+            .iter()
+            .filter_map(move |direction| self.step(tile, *direction))
+    }
+
+    /// The connected groups of tiles that pass `keep`
+    fn regions<F: Fn(Tile) -> bool>(&self, keep: F) -> Vec<Vec<Tile>> {
+        let mut seen = HashSet::new();
+        let mut regions = Vec::new();
+        for start in self.tiles() {
+            if !keep(start) || !seen.insert(start) {
+                continue;
+            }
+            let mut region = Vec::new();
+            let mut queue = VecDeque::from([start]);
+            while let Some(tile) = queue.pop_front() {
+                region.push(tile);
+                for next in self.neighbours(tile) {
+                    if keep(next) && seen.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+            regions.push(region);
+        }
+        regions
+    }
+
+    /// Walks from `tile` along `direction` until the next step would leave pathable ground,
+    /// giving up once it's gone further than a choke could be wide
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn walk(&self, tile: Tile, direction: (isize, isize)) -> Option<(Tile, usize)> {
+        let mut last = tile;
+        for steps in 0..=MAX_CHOKE_WIDTH as usize {
+            match self.step(last, direction) {
+                Some(next) if self.is_pathable(next) => last = next,
+                _ => return Some((last, steps)),
+            }
+        }
+        None
+    }
+
+    /// The narrowest line across the pathable ground through `tile`, if it's narrow enough to matter.
+    /// Too narrow to be a choke is still given back, so a corner doesn't look open.
+    #[allow(clippy::cast_precision_loss)]
+    fn crossing(&self, tile: Tile) -> Option<Crossing> {
+        CROSSINGS
+            .iter()
+            .filter_map(|&(dx, dy)| {
+                let (a, forward) = self.walk(tile, (dx, dy))?;
+                let (b, back) = self.walk(tile, (-dx, -dy))?;
+                let step = if dx != 0 && dy != 0 {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let width = (forward + back + 1) as f32 * step;
+                (width <= MAX_CHOKE_WIDTH).then_some(Crossing {
+                    ends: (b, a),
+                    width,
+                    direction: (dx, dy),
+                })
+            })
+            .min_by(|a, b| a.width.total_cmp(&b.width))
+    }
+
+    /// Whether the ground opens up a few tiles out on both sides of a crossing
+    fn opens_up(&self, tile: Tile, crossing: &Crossing) -> bool {
+        let (dx, dy) = crossing.direction;
+        [(-dy, dx), (dy, -dx)].iter().all(|&(nx, ny)| {
+            let mut out = Some(tile);
+            for _ in 0..CHOKE_DEPTH {
+                out = out.and_then(|t| self.step(t, (nx, ny)));
+            }
+            out.filter(|t| self.is_pathable(*t)).is_some_and(|t| {
+                self.crossing(t)
+                    .is_none_or(|c| c.width >= crossing.width + CHOKE_OPENING)
+            })
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Crossing {
+    ends: (Tile, Tile),
+    width: f32,
+    direction: (isize, isize),
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn middle(tiles: &[Tile]) -> Point2 {
+    let count = tiles.len().max(1) as f32;
+    let (x, y) = tiles.iter().fold((0.0, 0.0), |(x, y), (tx, ty)| {
+        (x + *tx as f32, y + *ty as f32)
+    });
+    Point2::new(x / count + 0.5, y / count + 0.5)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn tile_center((x, y): Tile) -> Point2 {
+    Point2::new(x as f32 + 0.5, y as f32 + 0.5)
+}
+
+/// how far apart the furthest two tiles are, counting the tiles themselves
+fn spread(tiles: &[Tile]) -> f32 {
+    tiles
+        .iter()
+        .flat_map(|a| {
+            tiles
+                .iter()
+                .map(move |b| tile_center(*a).distance(tile_center(*b)))
+        })
+        .fold(0.0, f32::max)
+        + 1.0
+}
+
+/// Sloped ground between two levels, which can't be built on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ramp {
+    pub tiles: Vec<Tile>,
+    /// the buildable high ground touching the ramp
+    pub upper: Vec<Tile>,
+    /// the buildable low ground touching the ramp
+    pub lower: Vec<Tile>,
+    pub top: Point2,
+    pub bottom: Point2,
+    /// how wide the top is, in tiles
+    pub width: f32,
+}
+
+/// A narrow gap in the pathable ground that's wider on both sides
+#[derive(Debug, Clone, PartialEq)]
+pub struct Choke {
+    pub center: Point2,
+    /// the last pathable tile at either side
+    pub ends: (Point2, Point2),
+    pub width: f32,
+}
+
+/// What we know about the map's shape, worked out once at the start
+#[derive(Debug, Clone, Default)]
+pub struct MapAnalysis {
+    pub ramps: Vec<Ramp>,
+    pub chokes: Vec<Choke>,
+    /// index into `ramps` of the one leading out of our main
+    pub main_ramp: Option<usize>,
+    /// index into `chokes` of the way into our natural
+    pub natural_choke: Option<usize>,
+}
+
+impl MapAnalysis {
+    pub fn new(terrain: &TerrainGrids, main: Point2, natural: Point2) -> Self {
+        let ramps = find_ramps(terrain);
+        let chokes = find_chokes(terrain);
+
+        let main_height = PlacementGrid::tile_at(main).map_or(0, |t| terrain.elevation(t));
+        let main_ramp = ramps
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.upper.iter().any(|t| terrain.elevation(*t) >= main_height))
+            .min_by(|(_, a), (_, b)| a.top.distance(main).total_cmp(&b.top.distance(main)))
+            .map(|(i, _)| i);
+
+        let ramp_bottom = main_ramp.map(|i| ramps[i].bottom);
+        let natural_choke = chokes
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| {
+                ramp_bottom.is_none_or(|b| c.center.distance(b) > RAMP_CHOKE_SEPARATION)
+            })
+            .min_by(|(_, a), (_, b)| {
+                a.center
+                    .distance(natural)
+                    .total_cmp(&b.center.distance(natural))
+            })
+            .map(|(i, _)| i);
+
+        Self {
+            ramps,
+            chokes,
+            main_ramp,
+            natural_choke,
+        }
+    }
+
+    pub fn main_ramp(&self) -> Option<&Ramp> {
+        self.main_ramp.and_then(|i| self.ramps.get(i))
+    }
+
+    pub fn natural_choke(&self) -> Option<&Choke> {
+        self.natural_choke.and_then(|i| self.chokes.get(i))
+    }
+}
+
+/// Pathable ground we can't build on that climbs from one level to another
+fn find_ramps(terrain: &TerrainGrids) -> Vec<Ramp> {
+    terrain
+        .regions(|t| terrain.is_pathable(t) && !terrain.is_placeable(t))
+        .into_iter()
+        .filter(|tiles| tiles.len() >= MIN_RAMP_CELLS)
+        .filter_map(|tiles| {
+            let heights = tiles.iter().map(|t| terrain.elevation(*t));
+            let (low, high) = (heights.clone().min()?, heights.max()?);
+            if high - low < MIN_RAMP_RISE {
+                return None;
+            }
+            let mut edge: Vec<Tile> = tiles
+                .iter()
+                .flat_map(|t| terrain.neighbours(*t))
+                .filter(|t| terrain.is_pathable(*t) && terrain.is_placeable(*t))
+                .collect();
+            edge.sort_unstable();
+            edge.dedup();
+            let (upper, lower): (Vec<Tile>, Vec<Tile>) = edge
+                .into_iter()
+                .filter(|t| terrain.elevation(*t) >= high || terrain.elevation(*t) <= low)
+                .partition(|t| terrain.elevation(*t) >= high);
+            if upper.is_empty() || lower.is_empty() {
+                return None;
+            }
+            Some(Ramp {
+                top: middle(&upper),
+                bottom: middle(&lower),
+                width: spread(&upper),
+                tiles,
+                upper,
+                lower,
+            })
+        })
+        .collect()
+}
+
+/// Narrow ground that opens up on both sides, one choke for each patch of it at its narrowest
+fn find_chokes(terrain: &TerrainGrids) -> Vec<Choke> {
+    let narrow: HashSet<Tile> = terrain
+        .tiles()
+        .filter(|t| terrain.is_pathable(*t))
+        .filter(|t| {
+            terrain
+                .crossing(*t)
+                .is_some_and(|c| c.width >= MIN_CHOKE_WIDTH && terrain.opens_up(*t, &c))
+        })
+        .collect();
+    terrain
+        .regions(|t| narrow.contains(&t))
+        .into_iter()
+        .filter_map(|tiles| {
+            let crossing = tiles
+                .iter()
+                .filter_map(|t| terrain.crossing(*t))
+                .min_by(|a, b| a.width.total_cmp(&b.width))?;
+            Some(Choke {
+                center: middle(&[crossing.ends.0, crossing.ends.1]),
+                ends: (tile_center(crossing.ends.0), tile_center(crossing.ends.1)),
+                width: crossing.width,
+            })
+        })
+        .collect()
+}
+
+impl ReBiCycler {
+    /// the pathing, placement and height grids, copied out for the analysis
+    pub fn terrain_grids(&self) -> TerrainGrids {
+        let (width, height) = (self.game_info.map_size.x, self.game_info.map_size.y);
+        let cells = || (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)));
+        TerrainGrids::new(
+            width,
+            height,
+            cells().map(|cell| self.is_pathable(cell)).collect(),
+            cells().map(|cell| self.is_placeable(cell)).collect(),
+            cells()
+                .map(|cell| self.game_info.terrain_height[cell])
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draws a map from rows of characters, top row first.
+    /// `#` is a wall, `.` low ground, `^` high ground, and `/` a ramp
+    /// climbing from low to high going right.
+    fn terrain(rows: &[String]) -> TerrainGrids {
+        let (width, height) = (rows[0].len(), rows.len());
+        let (mut pathable, mut placeable, mut elevation) = (Vec::new(), Vec::new(), Vec::new());
+        for row in rows.iter().rev() {
+            let mut climbed = 0;
+            for c in row.chars() {
+                pathable.push(c != '#');
+                placeable.push(c == '.' || c == '^');
+                elevation.push(match c {
+                    '^' => 100,
+                    '/' => {
+                        climbed += 12;
+                        44 + climbed
+                    }
+                    _ => 50,
+                });
+            }
+        }
+        TerrainGrids::new(width, height, pathable, placeable, elevation)
+    }
+
+    fn rows(rows: &[&str]) -> Vec<String> {
+        rows.iter().map(|r| (*r).to_string()).collect()
+    }
+
+    #[test]
+    fn ramp_goes_from_low_to_high() {
+        let map = terrain(&rows(&[
+            "####################",
+            "#.......####^^^^^^^#",
+            "#.......////^^^^^^^#",
+            "#.......////^^^^^^^#",
+            "#.......####^^^^^^^#",
+            "####################",
+        ]));
+        let ramps = find_ramps(&map);
+        assert_eq!(ramps.len(), 1, "{ramps:?}");
+        let ramp = &ramps[0];
+        assert!(ramp.top.x > 12.0 && ramp.bottom.x < 8.0, "{ramp:?}");
+        assert!((3.0..=4.5).contains(&ramp.width), "{ramp:?}");
+    }
+
+    #[test]
+    fn flat_unbuildable_ground_isnt_a_ramp() {
+        let mut map = terrain(&rows(&[
+            "##########",
+            "#........#",
+            "#..////..#",
+            "#........#",
+            "##########",
+        ]));
+        map.elevation.iter_mut().for_each(|h| *h = 50);
+        assert!(find_ramps(&map).is_empty());
+    }
+
+    #[test]
+    fn gap_in_a_wall_is_a_choke() {
+        // two rooms with a five wide gap between them, and a dead end that doesn't count
+        let room = format!("#{}#", ".".repeat(28));
+        let wall = format!("{}{}{}", "#".repeat(12), ".".repeat(5), "#".repeat(13));
+        let mut map = vec!["#".repeat(30)];
+        map.extend(std::iter::repeat_n(room.clone(), 10));
+        map.extend([wall.clone(), wall]);
+        map.extend(std::iter::repeat_n(room, 10));
+        map.push("#".repeat(30));
+
+        let chokes = find_chokes(&terrain(&map));
+        assert_eq!(chokes.len(), 1, "{chokes:?}");
+        let choke = &chokes[0];
+        assert!((choke.width - 5.0).abs() < 0.1, "{choke:?}");
+        assert!((choke.center.x - 14.5).abs() < 0.6, "{choke:?}");
+    }
+
+    #[test]
+    fn entrances_are_the_nearest_ones() {
+        // high ground main on the left, down the ramp into a room with a gap out the bottom
+        let mut map = rows(&[
+            "##############################",
+            "#^^^^^^^^####................#",
+            "#^^^^^^^^////................#",
+            "#^^^^^^^^////................#",
+            "#^^^^^^^^####................#",
+            "#^^^^^^^^####................#",
+            "#########################.....",
+        ]);
+        let gap = format!("{}{}{}", "#".repeat(20), ".".repeat(5), "#".repeat(5));
+        map[6] = gap.clone();
+        map.push(gap);
+        let below = format!("#{}#", ".".repeat(28));
+        map.extend(std::iter::repeat_n(below, 8));
+        map.push("#".repeat(30));
+
+        let analysis = MapAnalysis::new(
+            &terrain(&map),
+            Point2::new(4.5, 13.5),
+            Point2::new(20.5, 13.5),
+        );
+        let ramp = analysis.main_ramp().unwrap();
+        assert!(ramp.top.x < ramp.bottom.x, "{ramp:?}");
+        let choke = analysis.natural_choke().unwrap();
+        assert!((choke.center.x - 22.5).abs() < 0.6, "{choke:?}");
+    }
+}
@@ -50,8 +50,8 @@ impl ReBiCycler {
     pub fn map_siting(&mut self, frame_no: usize) {
         let mut image = self.pathing_map(255);
 
-        for (point, bl) in self.siting_director.iter() {
-            let contained_points = bl.size().contained_points(*point);
+        for bl in self.siting_director.iter() {
+            let contained_points = bl.size().contained_points(bl.location());
             let color = bl.color(200);
             for (x, y) in contained_points {
                 if point_within_image(&self.game_info.map_size, (x, y)) {
@@ -13,6 +13,8 @@ use crate::assignment_manager::{AssignmentError, AssignmentManager, Assigns, Com
 const MINERAL_MINE_DISTANCE: f32 = 1.0;
 const GAS_MINE_DISTANCE: f32 = 2.5;
 const RETURN_CARGO_DISTANCE: f32 = 2.9;
+const WORKERS_PER_MINERAL_PATCH: usize = 2;
+const WORKERS_PER_GAS: usize = 3;
 
 type MiningAssignmentError = AssignmentError<Miner, ResourcePairing, u64, JobId>;
 
@@ -226,9 +228,32 @@ impl MinerController {
             .map(super::assignment_manager::Identity::id)
     }
 
+    /// workers on mineral patches, not gas
+    pub fn mineral_miners(&self) -> impl Iterator<Item = u64> + use<'_> {
+        self.mining_manager
+            .iter_assignments()
+            .filter(|(_, pairing)| pairing.is_mineral())
+            .map(|(miner, _)| miner.id())
+    }
+
     pub fn saturation(&self) -> HashMap<&ResourcePairing, usize> {
         self.mining_manager.count_assignments()
     }
+
+    /// how many townhalls have every one of their mineral patches and geysers filled
+    pub fn saturated_bases(&self) -> usize {
+        let mut bases: HashMap<u64, bool> = HashMap::new();
+        for (pair, count) in self.saturation() {
+            let capacity = if pair.is_gas() {
+                WORKERS_PER_GAS
+            } else {
+                WORKERS_PER_MINERAL_PATCH
+            };
+            let full = bases.entry(pair.townhall.tag).or_insert(true);
+            *full &= count >= capacity;
+        }
+        bases.values().filter(|full| **full).count()
+    }
 }
 
 impl Display for MinerController {
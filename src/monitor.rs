@@ -1,4 +1,4 @@
-use crate::protoss_bot::ReBiCycler;
+use crate::{protoss_bot::ReBiCycler, upgrades::UpgradeLine};
 
 use itertools::Itertools;
 use rust_sc2::ids::{BuffId, UpgradeId};
@@ -57,8 +57,12 @@ impl ReBiCycler {
             frame_no
         );
         let header = format!(
-            "M: {} G: {} S:{}/{}",
-            self.minerals, self.vespene, self.supply_used, self.supply_cap
+            "M: {} G: {} S:{}/{} (blocked {:.0}s)",
+            self.minerals,
+            self.vespene,
+            self.supply_used,
+            self.supply_cap,
+            self.supply_blocks.seconds()
         );
         let spent = self.knowledge.total_spend;
         let reimbursed = self.knowledge.total_reimbursed;
@@ -217,57 +221,16 @@ impl ReBiCycler {
     }
 
     fn get_protoss_standard_upgrades(&self) -> Vec<String> {
-        let ground_weapons = [
-            UpgradeId::ProtossGroundWeaponsLevel1,
-            UpgradeId::ProtossGroundWeaponsLevel2,
-            UpgradeId::ProtossGroundWeaponsLevel3,
-        ]
-        .map(|u| {
-            if self.has_upgrade(u) {
-                WEAPONS_ICON
-            } else {
-                ""
-            }
-        })
-        .join("");
-
-        let ground_armor = [
-            UpgradeId::ProtossGroundArmorsLevel1,
-            UpgradeId::ProtossGroundArmorsLevel2,
-            UpgradeId::ProtossGroundArmorsLevel3,
-        ]
-        .map(|u| if self.has_upgrade(u) { ARMOR_ICON } else { "" })
-        .join("");
-
-        let air_weapons = [
-            UpgradeId::ProtossAirWeaponsLevel1,
-            UpgradeId::ProtossAirWeaponsLevel2,
-            UpgradeId::ProtossAirWeaponsLevel3,
-        ]
-        .map(|u| {
-            if self.has_upgrade(u) {
-                WEAPONS_ICON
-            } else {
-                ""
-            }
-        })
-        .join("");
-
-        let air_armor = [
-            UpgradeId::ProtossAirArmorsLevel1,
-            UpgradeId::ProtossAirArmorsLevel2,
-            UpgradeId::ProtossAirArmorsLevel3,
-        ]
-        .map(|u| if self.has_upgrade(u) { ARMOR_ICON } else { "" })
-        .join("");
-
-        let shields = [
-            UpgradeId::ProtossShieldsLevel1,
-            UpgradeId::ProtossShieldsLevel2,
-            UpgradeId::ProtossShieldsLevel3,
-        ]
-        .map(|u| if self.has_upgrade(u) { SHIELD_ICON } else { "" })
-        .join("");
+        let icons = |line: UpgradeLine, icon: &str| {
+            line.levels()
+                .map(|u| if self.has_upgrade(u) { icon } else { "" })
+                .join("")
+        };
+        let ground_weapons = icons(UpgradeLine::GroundWeapons, WEAPONS_ICON);
+        let ground_armor = icons(UpgradeLine::GroundArmor, ARMOR_ICON);
+        let air_weapons = icons(UpgradeLine::AirWeapons, WEAPONS_ICON);
+        let air_armor = icons(UpgradeLine::AirArmor, ARMOR_ICON);
+        let shields = icons(UpgradeLine::Shields, SHIELD_ICON);
 
         vec![
             format!("Ground: {ground_armor}{ground_weapons}"),
@@ -330,6 +293,16 @@ impl ReBiCycler {
             self.display_terminal
                 .write_line_to_pane("Army", &out, false);
         }
+
+        let gap = self.composition_gap();
+        if !gap.is_empty() {
+            self.display_terminal
+                .write_line_to_pane("Army", "Target:", false);
+        }
+        for line in gap {
+            self.display_terminal
+                .write_line_to_pane("Army", &format!("- {line}"), false);
+        }
     }
 
     fn display_construction(&mut self) {
@@ -0,0 +1,466 @@
+use std::collections::HashMap;
+
+use rust_sc2::{
+    action::Target,
+    ids::{AbilityId, BuffId, UnitTypeId},
+    prelude::Point2,
+    unit::Unit,
+};
+
+use crate::{
+    build_orders::{BuildOrderAction, Priority},
+    build_tree::BuildComponent,
+    protoss_bot::ReBiCycler,
+};
+
+const CHRONO_ENERGY: f32 = 50.0;
+const RECALL_ENERGY: f32 = 50.0;
+const OVERCHARGE_ENERGY: f32 = 50.0;
+/// what each nexus holds back while enemies are near one of our bases
+const THREAT_RESERVE: f32 = 50.0;
+/// enemies this close to a nexus are a threat to it
+pub const THREAT_RADIUS: f32 = 15.0;
+/// recall is shared between nexuses and has a long cooldown
+const RECALL_COOLDOWN: f32 = 130.0;
+/// not worth a recall for an army that's close enough to walk home
+const RECALL_MIN_DISTANCE: f32 = 40.0;
+const RECALL_MIN_ARMY: usize = 4;
+
+impl ReBiCycler {
+    /// Casts whatever the energy manager thinks the nexuses should cast this step
+    pub fn spend_nexus_energy(&mut self) {
+        let situation = self.energy_situation();
+        for energy_use in self.nexus_energy.plan(&situation) {
+            let (nexus, ability, target) = match energy_use {
+                EnergyUse::Chrono { nexus, target } => (
+                    nexus,
+                    AbilityId::EffectChronoBoostEnergyCost,
+                    Target::Tag(target),
+                ),
+                EnergyUse::Overcharge { nexus, battery } => (
+                    nexus,
+                    AbilityId::BatteryOverchargeBatteryOvercharge,
+                    Target::Tag(battery),
+                ),
+                EnergyUse::Recall { nexus, target } => {
+                    (nexus, AbilityId::EffectMassRecallNexus, Target::Pos(target))
+                }
+            };
+            if let Some(nexus) = self.units.my.townhalls.get(nexus) {
+                nexus.command(ability, target, false);
+            }
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn energy_situation(&self) -> EnergySituation {
+        let army = self.units.my.units.filter(|u| !u.is_worker());
+        EnergySituation {
+            time: self.time,
+            nexuses: self
+                .units
+                .my
+                .townhalls
+                .ready()
+                .iter()
+                .map(|n| NexusState {
+                    tag: n.tag(),
+                    position: n.position(),
+                    energy: n.energy().map_or(0.0, |e| e as f32),
+                })
+                .collect(),
+            chrono_targets: self
+                .units
+                .my
+                .structures
+                .iter()
+                .filter_map(ChronoTarget::from_unit)
+                .collect(),
+            batteries: self
+                .units
+                .my
+                .structures
+                .of_type(UnitTypeId::ShieldBattery)
+                .ready()
+                .iter()
+                .map(|b| Battery {
+                    tag: b.tag(),
+                    position: b.position(),
+                    overcharged: b.has_buff(BuffId::BatteryOvercharge),
+                })
+                .collect(),
+            threats: self
+                .units
+                .enemy
+                .units
+                .iter()
+                .filter(|u| !u.is_worker())
+                .map(Unit::position)
+                .collect(),
+            army: army.center().map(|center| (center, army.len())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NexusState {
+    pub tag: u64,
+    pub position: Point2,
+    pub energy: f32,
+}
+
+/// A structure that's busy with something we could chrono
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChronoTarget {
+    pub tag: u64,
+    pub unit_type: UnitTypeId,
+    pub ability: AbilityId,
+    pub boosted: bool,
+}
+impl ChronoTarget {
+    /// structures that aren't doing anything aren't worth boosting
+    fn from_unit(unit: &Unit) -> Option<Self> {
+        let (ability, _, _) = unit.order()?;
+        Some(Self {
+            tag: unit.tag(),
+            unit_type: unit.type_id(),
+            ability,
+            boosted: unit.has_buff(BuffId::ChronoBoostEnergyCost),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Battery {
+    pub tag: u64,
+    pub position: Point2,
+    pub overcharged: bool,
+}
+
+/// Everything the energy plan looks at, pulled out of the game once per step
+#[derive(Debug, Clone, Default)]
+pub struct EnergySituation {
+    pub time: f32,
+    pub nexuses: Vec<NexusState>,
+    pub chrono_targets: Vec<ChronoTarget>,
+    pub batteries: Vec<Battery>,
+    /// enemy fighting units we can see
+    pub threats: Vec<Point2>,
+    /// where our army is and how big it is
+    pub army: Option<(Point2, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnergyUse {
+    Chrono { nexus: u64, target: u64 },
+    Overcharge { nexus: u64, battery: u64 },
+    Recall { nexus: u64, target: Point2 },
+}
+
+/// Something the build asked to have chrono'd
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ChronoWish {
+    Ability(AbilityId),
+    Trainer(UnitTypeId),
+}
+
+/// Splits nexus energy between chrono, Battery Overcharge and Strategic Recall.
+/// Emergencies come first, then chrono goes to whatever the build cares about most.
+#[derive(Debug, Default)]
+pub struct NexusEnergyManager {
+    /// the chrono actions of the active build components, and how much they matter
+    wishes: HashMap<ChronoWish, Priority>,
+    /// what the active build components are producing, and how much they matter
+    production: HashMap<AbilityId, Priority>,
+    last_recall: Option<f32>,
+}
+
+impl NexusEnergyManager {
+    /// Takes note of what the active components want chrono'd and what they're making.
+    pub fn read_build(&mut self, active: &[BuildComponent]) {
+        self.wishes.clear();
+        self.production.clear();
+        for component in active {
+            let priority = component.priority();
+            match component.action() {
+                Some(BuildOrderAction::Chrono(ability)) => {
+                    raise(&mut self.wishes, ChronoWish::Ability(ability), priority);
+                }
+                Some(BuildOrderAction::ChronoWhatever(trainer)) => {
+                    raise(&mut self.wishes, ChronoWish::Trainer(trainer), priority);
+                }
+                Some(
+                    BuildOrderAction::Train(_, ability) | BuildOrderAction::Research(_, ability, _),
+                ) => raise(&mut self.production, ability, priority),
+                _ => {}
+            }
+        }
+    }
+
+    /// What to spend energy on this step. Each nexus casts at most once.
+    pub fn plan(&mut self, situation: &EnergySituation) -> Vec<EnergyUse> {
+        let mut energy: HashMap<u64, f32> = situation
+            .nexuses
+            .iter()
+            .map(|n| (n.tag, n.energy))
+            .collect();
+        let mut uses = Vec::new();
+
+        let threatened: Vec<&NexusState> = situation
+            .nexuses
+            .iter()
+            .filter(|n| {
+                situation
+                    .threats
+                    .iter()
+                    .any(|t| t.distance(n.position) < THREAT_RADIUS)
+            })
+            .collect();
+
+        // the army comes home to the caster, so it has to be the nexus in trouble
+        let recall_ready = self
+            .last_recall
+            .is_none_or(|last| situation.time - last >= RECALL_COOLDOWN);
+        if let (true, Some((army, size)), Some(home)) =
+            (recall_ready, situation.army, threatened.first())
+        {
+            if size >= RECALL_MIN_ARMY
+                && army.distance(home.position) > RECALL_MIN_DISTANCE
+                && energy.get(&home.tag).is_some_and(|e| *e >= RECALL_ENERGY)
+            {
+                spend(&mut energy, home.tag, RECALL_ENERGY);
+                uses.push(EnergyUse::Recall {
+                    nexus: home.tag,
+                    target: army,
+                });
+                self.last_recall = Some(situation.time);
+            }
+        }
+
+        for nexus in &threatened {
+            let battery = situation
+                .batteries
+                .iter()
+                .filter(|b| !b.overcharged && b.position.distance(nexus.position) < THREAT_RADIUS)
+                .filter(|b| {
+                    !uses.iter().any(
+                        |u| matches!(u, EnergyUse::Overcharge { battery, .. } if *battery == b.tag),
+                    )
+                })
+                .min_by(|a, b| {
+                    a.position
+                        .distance(nexus.position)
+                        .total_cmp(&b.position.distance(nexus.position))
+                });
+            if let (Some(battery), Some(caster)) =
+                (battery, richest(&energy, OVERCHARGE_ENERGY, &uses))
+            {
+                spend(&mut energy, caster, OVERCHARGE_ENERGY);
+                uses.push(EnergyUse::Overcharge {
+                    nexus: caster,
+                    battery: battery.tag,
+                });
+            }
+        }
+
+        let reserve = if threatened.is_empty() {
+            0.0
+        } else {
+            THREAT_RESERVE
+        };
+        for target in self.chrono_order(&situation.chrono_targets) {
+            let Some(caster) = richest(&energy, CHRONO_ENERGY + reserve, &uses) else {
+                break;
+            };
+            spend(&mut energy, caster, CHRONO_ENERGY);
+            uses.push(EnergyUse::Chrono {
+                nexus: caster,
+                target: target.tag,
+            });
+        }
+        uses
+    }
+
+    /// Unboosted targets the build asked for, most important first.
+    /// Ties go to whatever the structure is making that matters most to the build.
+    fn chrono_order<'a>(&self, targets: &'a [ChronoTarget]) -> Vec<&'a ChronoTarget> {
+        let mut scored: Vec<(Priority, Priority, &ChronoTarget)> = targets
+            .iter()
+            .filter(|t| !t.boosted)
+            .filter_map(|t| {
+                let wish = [
+                    self.wishes.get(&ChronoWish::Ability(t.ability)),
+                    self.wishes.get(&ChronoWish::Trainer(t.unit_type)),
+                ]
+                .into_iter()
+                .flatten()
+                .max()?;
+                let making = self
+                    .production
+                    .get(&t.ability)
+                    .copied()
+                    .unwrap_or(Priority::Low);
+                Some((*wish, making, t))
+            })
+            .collect();
+        scored.sort_by_key(|(wish, making, _)| std::cmp::Reverse((*wish, *making)));
+        scored.into_iter().map(|(_, _, t)| t).collect()
+    }
+}
+
+/// keeps the highest priority anything asked for
+fn raise<K: std::hash::Hash + Eq>(map: &mut HashMap<K, Priority>, key: K, priority: Priority) {
+    let entry = map.entry(key).or_insert(priority);
+    *entry = (*entry).max(priority);
+}
+
+/// the nexus with the most energy that has at least `needed` and hasn't cast yet
+fn richest(energy: &HashMap<u64, f32>, needed: f32, uses: &[EnergyUse]) -> Option<u64> {
+    energy
+        .iter()
+        .filter(|(tag, e)| **e >= needed && !uses.iter().any(|u| caster_of(u) == **tag))
+        .max_by(|a, b| a.1.total_cmp(b.1).then(b.0.cmp(a.0)))
+        .map(|(tag, _)| *tag)
+}
+
+fn spend(energy: &mut HashMap<u64, f32>, nexus: u64, amount: f32) {
+    if let Some(e) = energy.get_mut(&nexus) {
+        *e -= amount;
+    }
+}
+
+const fn caster_of(energy_use: &EnergyUse) -> u64 {
+    match energy_use {
+        EnergyUse::Chrono { nexus, .. }
+        | EnergyUse::Overcharge { nexus, .. }
+        | EnergyUse::Recall { nexus, .. } => *nexus,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nexus(tag: u64, x: f32, energy: f32) -> NexusState {
+        NexusState {
+            tag,
+            position: Point2::new(x, 0.0),
+            energy,
+        }
+    }
+
+    fn target(tag: u64, unit_type: UnitTypeId, ability: AbilityId) -> ChronoTarget {
+        ChronoTarget {
+            tag,
+            unit_type,
+            ability,
+            boosted: false,
+        }
+    }
+
+    #[test]
+    fn chrono_follows_build_priority() {
+        let mut manager = NexusEnergyManager::default();
+        manager.read_build(&[
+            BuildComponent::doing(
+                BuildOrderAction::ChronoWhatever(UnitTypeId::Gateway),
+                Priority::Normal,
+            ),
+            BuildComponent::doing(
+                BuildOrderAction::Chrono(AbilityId::ResearchWarpGate),
+                Priority::High,
+            ),
+            BuildComponent::doing(
+                BuildOrderAction::Train(UnitTypeId::Stalker, AbilityId::GatewayTrainStalker),
+                Priority::High,
+            ),
+        ]);
+        let mut boosted = target(4, UnitTypeId::Gateway, AbilityId::GatewayTrainStalker);
+        boosted.boosted = true;
+        let situation = EnergySituation {
+            nexuses: vec![nexus(1, 0.0, 60.0), nexus(2, 50.0, 120.0)],
+            chrono_targets: vec![
+                target(10, UnitTypeId::Gateway, AbilityId::GatewayTrainZealot),
+                target(11, UnitTypeId::Gateway, AbilityId::GatewayTrainStalker),
+                target(12, UnitTypeId::CyberneticsCore, AbilityId::ResearchWarpGate),
+                target(13, UnitTypeId::Nexus, AbilityId::NexusTrainProbe),
+                boosted,
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            manager.plan(&situation),
+            vec![
+                EnergyUse::Chrono {
+                    nexus: 2,
+                    target: 12
+                },
+                EnergyUse::Chrono {
+                    nexus: 1,
+                    target: 11
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn threats_come_first() {
+        let mut manager = NexusEnergyManager::default();
+        manager.read_build(&[BuildComponent::doing(
+            BuildOrderAction::ChronoWhatever(UnitTypeId::Nexus),
+            Priority::Urgent,
+        )]);
+        let mut situation = EnergySituation {
+            time: 300.0,
+            nexuses: vec![
+                nexus(1, 0.0, 100.0),
+                nexus(2, 50.0, 90.0),
+                nexus(3, 100.0, 75.0),
+            ],
+            chrono_targets: vec![target(13, UnitTypeId::Nexus, AbilityId::NexusTrainProbe)],
+            batteries: vec![Battery {
+                tag: 20,
+                position: Point2::new(3.0, 0.0),
+                overcharged: false,
+            }],
+            threats: vec![Point2::new(5.0, 0.0)],
+            army: Some((Point2::new(150.0, 0.0), 10)),
+        };
+
+        // recall to the base in trouble, overcharge from the richest of the rest,
+        // and nobody has enough to chrono while holding the reserve
+        assert_eq!(
+            manager.plan(&situation),
+            vec![
+                EnergyUse::Recall {
+                    nexus: 1,
+                    target: Point2::new(150.0, 0.0)
+                },
+                EnergyUse::Overcharge {
+                    nexus: 2,
+                    battery: 20
+                },
+            ]
+        );
+
+        // a minute later recall is on cooldown
+        situation.time = 360.0;
+        assert_eq!(
+            manager.plan(&situation),
+            vec![EnergyUse::Overcharge {
+                nexus: 1,
+                battery: 20
+            }]
+        );
+
+        // and with the threat gone chrono is back on
+        situation.threats.clear();
+        assert_eq!(
+            manager.plan(&situation),
+            vec![EnergyUse::Chrono {
+                nexus: 1,
+                target: 13
+            }]
+        );
+    }
+}
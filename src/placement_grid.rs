@@ -0,0 +1,307 @@
+use std::collections::VecDeque;
+
+use rust_sc2::prelude::*;
+
+/// A map tile as (x, y), counting from the bottom left
+pub type Tile = (usize, usize);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cell {
+    /// the terrain lets us build here
+    pub buildable: bool,
+    /// the terrain lets ground units walk here
+    pub pathable: bool,
+    /// the bottom left tile of the site covering this cell, planned or built on
+    pub site: Option<Tile>,
+    /// how many of our power sources reach the middle of this cell
+    pub power: u8,
+}
+
+/// One cell per map tile, so whether a footprint fits is a handful of lookups
+/// instead of comparing floats against every site we know about.
+#[derive(Debug, Clone, Default)]
+pub struct PlacementGrid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    /// power sources we've added, so losing one we never counted doesn't depower anything
+    sources: Vec<(Point2, f32)>,
+}
+
+impl PlacementGrid {
+    /// `buildable` and `pathable` go row by row from the bottom, like `warp_terrain` reads it
+    pub fn new(width: usize, height: usize, buildable: &[bool], pathable: &[bool]) -> Self {
+        let cells = (0..width * height)
+            .map(|i| Cell {
+                buildable: buildable.get(i).copied().unwrap_or(false),
+                pathable: pathable.get(i).copied().unwrap_or(false),
+                ..Cell::default()
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            cells,
+            sources: Vec::new(),
+        }
+    }
+
+    /// all buildable, all walkable, for tests that don't care about terrain
+    #[cfg(test)]
+    pub fn open(width: usize, height: usize) -> Self {
+        let everywhere = vec![true; width * height];
+        Self::new(width, height, &everywhere, &everywhere)
+    }
+
+    const fn index(&self, (x, y): Tile) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    pub fn cell(&self, tile: Tile) -> Option<&Cell> {
+        self.index(tile).map(|i| &self.cells[i])
+    }
+
+    /// the tile a point falls in
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn tile_at(point: Point2) -> Option<Tile> {
+        (point.x >= 0.0 && point.y >= 0.0).then_some((point.x as usize, point.y as usize))
+    }
+
+    /// every tile of a square `size` wide with its bottom left at `corner`
+    pub fn footprint(corner: Tile, size: usize) -> impl Iterator<Item = Tile> {
+        (0..size).flat_map(move |dx| (0..size).map(move |dy| (corner.0 + dx, corner.1 + dy)))
+    }
+
+    /// Can a square `size` wide go here: all buildable, and no site has it yet
+    pub fn fits(&self, corner: Tile, size: usize) -> bool {
+        Self::footprint(corner, size).all(|tile| {
+            self.cell(tile)
+                .is_some_and(|c| c.buildable && c.site.is_none())
+        })
+    }
+
+    /// Hands the footprint to the site at `corner`.
+    /// Townhalls and geysers skip the terrain check, the placement grid doesn't count them as buildable.
+    /// Gives back false, changing nothing, if any of it is off the map or already someone else's.
+    pub fn claim(&mut self, corner: Tile, size: usize, check_terrain: bool) -> bool {
+        let free = Self::footprint(corner, size).all(|tile| {
+            self.cell(tile)
+                .is_some_and(|c| c.site.is_none() && (c.buildable || !check_terrain))
+        });
+        if free {
+            for tile in Self::footprint(corner, size) {
+                if let Some(i) = self.index(tile) {
+                    self.cells[i].site = Some(corner);
+                }
+            }
+        }
+        free
+    }
+
+    /// Hands back whatever of the footprint the site at `corner` had
+    pub fn release(&mut self, corner: Tile, size: usize) {
+        for tile in Self::footprint(corner, size) {
+            if let Some(i) = self.index(tile) {
+                if self.cells[i].site == Some(corner) {
+                    self.cells[i].site = None;
+                }
+            }
+        }
+    }
+
+    /// Whether the terrain alone would let a square `size` wide go here
+    pub fn is_buildable(&self, corner: Tile, size: usize) -> bool {
+        Self::footprint(corner, size).all(|tile| self.cell(tile).is_some_and(|c| c.buildable))
+    }
+
+    /// the sites with any of their footprint in this square
+    pub fn sites_under(&self, corner: Tile, size: usize) -> Vec<Tile> {
+        let mut sites: Vec<Tile> = Self::footprint(corner, size)
+            .filter_map(|tile| self.cell(tile).and_then(|c| c.site))
+            .collect();
+        sites.sort_unstable();
+        sites.dedup();
+        sites
+    }
+
+    /// the site covering this point, if any
+    pub fn site_at(&self, point: Point2) -> Option<Tile> {
+        Self::tile_at(point)
+            .and_then(|tile| self.cell(tile))
+            .and_then(|c| c.site)
+    }
+
+    pub fn is_powered(&self, point: Point2) -> bool {
+        Self::tile_at(point)
+            .and_then(|tile| self.cell(tile))
+            .is_some_and(|c| c.power > 0)
+    }
+
+    /// whether a ground unit could stand here, given what else `blocked` says is in the way
+    fn is_open(&self, tile: Tile, blocked: &impl Fn(Tile) -> bool) -> bool {
+        self.cell(tile).is_some_and(|c| c.pathable) && !blocked(tile)
+    }
+
+    /// The open tile closest to `point`, looking at most `reach` tiles away.
+    /// Points tend to sit on something, a nexus or a mineral field, so this finds the ground next to it.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn nearest_open(
+        &self,
+        point: Point2,
+        reach: usize,
+        blocked: impl Fn(Tile) -> bool,
+    ) -> Option<Tile> {
+        let (x, y) = Self::tile_at(point)?;
+        let xs = x.saturating_sub(reach)..=x + reach;
+        let ys = y.saturating_sub(reach)..=y + reach;
+        xs.flat_map(|x| ys.clone().map(move |y| (x, y)))
+            .filter(|tile| self.is_open(*tile, &blocked))
+            .min_by(|a, b| {
+                let middle = |(x, y): Tile| Point2::new(x as f32 + 0.5, y as f32 + 0.5);
+                middle(*a)
+                    .distance_squared(point)
+                    .total_cmp(&middle(*b).distance_squared(point))
+            })
+    }
+
+    /// Flood fills out from `from`, going around anything `blocked` says is in the way.
+    /// Only steps straight across edges, units can't squeeze between two corners that touch.
+    pub fn connected(&self, from: Tile, to: Tile, blocked: impl Fn(Tile) -> bool) -> bool {
+        if !self.is_open(from, &blocked) || !self.is_open(to, &blocked) {
+            return false;
+        }
+        let mut seen = vec![false; self.cells.len()];
+        let mut frontier = VecDeque::from([from]);
+        if let Some(i) = self.index(from) {
+            seen[i] = true;
+        }
+        while let Some((x, y)) = frontier.pop_front() {
+            if (x, y) == to {
+                return true;
+            }
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for next in neighbours {
+                let Some(i) = self.index(next) else {
+                    continue;
+                };
+                if !seen[i] && self.is_open(next, &blocked) {
+                    seen[i] = true;
+                    frontier.push_back(next);
+                }
+            }
+        }
+        false
+    }
+
+    /// Adds or takes away a power source.
+    /// Gives back whether anything changed, taking away one we never added doesn't.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn change_power(&mut self, center: Point2, radius: f32, turned_on: bool) -> bool {
+        if turned_on {
+            self.sources.push((center, radius));
+        } else if let Some(i) = self
+            .sources
+            .iter()
+            .position(|(p, r)| *p == center && (r - radius).abs() < f32::EPSILON)
+        {
+            self.sources.swap_remove(i);
+        } else {
+            return false;
+        }
+
+        let corner = Self::tile_at(center.offset(-radius, -radius)).unwrap_or((0, 0));
+        let top = Self::tile_at(center.offset(radius, radius)).unwrap_or((0, 0));
+        for x in corner.0..=top.0.min(self.width.saturating_sub(1)) {
+            for y in corner.1..=top.1.min(self.height.saturating_sub(1)) {
+                let middle = Point2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if middle.distance(center) > radius {
+                    continue;
+                }
+                if let Some(i) = self.index((x, y)) {
+                    let power = &mut self.cells[i].power;
+                    *power = if turned_on {
+                        power.saturating_add(1)
+                    } else {
+                        power.saturating_sub(1)
+                    };
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_footprint_only_goes_down_once() {
+        let mut grid = PlacementGrid::open(20, 20);
+        assert!(grid.fits((4, 4), 3));
+        assert!(grid.claim((4, 4), 3, true));
+        assert!(!grid.fits((4, 4), 3));
+        // one tile of overlap is enough
+        assert!(!grid.claim((6, 6), 3, true));
+        assert!(grid.claim((7, 4), 3, true));
+        assert_eq!(grid.site_at(Point2::new(6.9, 6.9)), Some((4, 4)));
+        assert_eq!(grid.site_at(Point2::new(7.0, 6.9)), Some((7, 4)));
+        // and nothing hangs off the map
+        assert!(!grid.fits((18, 18), 3));
+    }
+
+    #[test]
+    fn terrain_matters_unless_told_otherwise() {
+        let mut buildable = [true; 400];
+        buildable[5 * 20 + 5] = false;
+        let mut grid = PlacementGrid::new(20, 20, &buildable, &[true; 400]);
+        assert!(!grid.fits((4, 4), 3));
+        assert!(!grid.claim((4, 4), 3, true));
+        assert!(grid.claim((3, 3), 5, false));
+    }
+
+    #[test]
+    fn overlapping_pylons_keep_the_power_on() {
+        let mut grid = PlacementGrid::open(20, 20);
+        let gateway = Point2::new(10.5, 10.5);
+        let (left, right) = (Point2::new(6.0, 10.0), Point2::new(15.0, 10.0));
+        assert!(grid.change_power(left, 6.5, true));
+        assert!(grid.change_power(right, 6.5, true));
+        assert!(grid.is_powered(gateway));
+
+        assert!(grid.change_power(left, 6.5, false));
+        assert!(grid.is_powered(gateway));
+        // a prism that never phased here doesn't count
+        assert!(!grid.change_power(right, 3.75, false));
+        assert!(grid.is_powered(gateway));
+        assert!(grid.change_power(right, 6.5, false));
+        assert!(!grid.is_powered(gateway));
+    }
+
+    #[test]
+    fn a_closed_ring_cuts_off_the_middle() {
+        let grid = PlacementGrid::open(20, 20);
+        let ring =
+            |(x, y): Tile| (5..=9).contains(&x) && (5..=9).contains(&y) && !(x == 7 && y == 7);
+        assert!(!grid.connected((7, 7), (0, 0), ring));
+        // corners touching diagonally still close it off
+        let diamond = |(x, y): Tile| x.abs_diff(7) + y.abs_diff(7) == 2;
+        assert!(!grid.connected((7, 7), (0, 0), diamond));
+        // one missing tile opens it back up
+        let gap = |(x, y): Tile| ring((x, y)) && !(x == 7 && y == 5) && !(x == 7 && y == 6);
+        assert!(grid.connected((7, 7), (0, 0), gap));
+        assert_eq!(
+            grid.nearest_open(Point2::new(7.5, 6.2), 2, ring),
+            Some((7, 7))
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use rust_sc2::{
+    ids::{AbilityId, UnitTypeId},
+    unit::Unit,
+};
+
+use crate::{
+    budget::Budget, build_orders::BuildOrderAction, build_tree::BuildComponent, errors::BuildError,
+    protoss_bot::ReBiCycler, tech_tree::unit_requires,
+};
+
+/// How much of the army a unit type should be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quota {
+    /// a share of everything with a percentage, the percentages don't have to add up to 100
+    Percent(u8),
+    /// a fixed number, made before the shares get topped up
+    Count(u16),
+}
+
+/// The ability that makes `unit` from `producer`, if it can
+#[must_use]
+pub const fn train_ability(producer: UnitTypeId, unit: UnitTypeId) -> Option<AbilityId> {
+    use AbilityId as A;
+    use UnitTypeId as U;
+    let ability = match (producer, unit) {
+        (U::Gateway, U::Zealot) => A::GatewayTrainZealot,
+        (U::Gateway, U::Stalker) => A::GatewayTrainStalker,
+        (U::Gateway, U::Sentry) => A::GatewayTrainSentry,
+        (U::Gateway, U::Adept) => A::TrainAdept,
+        (U::Gateway, U::HighTemplar) => A::GatewayTrainHighTemplar,
+        (U::Gateway, U::DarkTemplar) => A::GatewayTrainDarkTemplar,
+        (U::WarpGate, U::Zealot) => A::WarpGateTrainZealot,
+        (U::WarpGate, U::Stalker) => A::WarpGateTrainStalker,
+        (U::WarpGate, U::Sentry) => A::WarpGateTrainSentry,
+        (U::WarpGate, U::Adept) => A::TrainWarpAdept,
+        (U::WarpGate, U::HighTemplar) => A::WarpGateTrainHighTemplar,
+        (U::WarpGate, U::DarkTemplar) => A::WarpGateTrainDarkTemplar,
+        (U::RoboticsFacility, U::Observer) => A::RoboticsFacilityTrainObserver,
+        (U::RoboticsFacility, U::WarpPrism) => A::RoboticsFacilityTrainWarpPrism,
+        (U::RoboticsFacility, U::Immortal) => A::RoboticsFacilityTrainImmortal,
+        (U::RoboticsFacility, U::Colossus) => A::RoboticsFacilityTrainColossus,
+        (U::RoboticsFacility, U::Disruptor) => A::TrainDisruptor,
+        (U::Stargate, U::Phoenix) => A::StargateTrainPhoenix,
+        (U::Stargate, U::Oracle) => A::StargateTrainOracle,
+        (U::Stargate, U::VoidRay) => A::StargateTrainVoidRay,
+        (U::Stargate, U::Tempest) => A::StargateTrainTempest,
+        (U::Stargate, U::Carrier) => A::StargateTrainCarrier,
+        _ => return None,
+    };
+    Some(ability)
+}
+
+/// The army we're aiming for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Composition {
+    targets: Vec<(UnitTypeId, Quota)>,
+}
+
+impl Composition {
+    pub fn new(targets: &[(UnitTypeId, Quota)]) -> Self {
+        Self {
+            targets: targets.to_vec(),
+        }
+    }
+
+    /// Every `Produce` in the active part of the build, in tree order.
+    /// A unit named twice keeps the first quota.
+    pub fn from_build(active: &[BuildComponent]) -> Self {
+        let mut targets: Vec<(UnitTypeId, Quota)> = Vec::new();
+        for component in active {
+            if let Some(BuildOrderAction::Produce(unit, quota)) = component.action() {
+                if !targets.iter().any(|(u, _)| *u == unit) {
+                    targets.push((unit, quota));
+                }
+            }
+        }
+        Self { targets }
+    }
+
+    /// the units with a target, in the order they were given
+    pub fn units(&self) -> impl Iterator<Item = UnitTypeId> + '_ {
+        self.targets.iter().map(|(unit, _)| *unit)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    fn is_share(&self, unit: UnitTypeId) -> bool {
+        self.targets
+            .iter()
+            .any(|(u, quota)| *u == unit && matches!(quota, Quota::Percent(_)))
+    }
+
+    /// How many of the unit we'd want with one more unit in the army, less what we have.
+    /// Picking the biggest each time keeps the shares close as the army grows.
+    #[allow(clippy::cast_precision_loss)]
+    fn deficit(&self, unit: UnitTypeId, army: &HashMap<UnitTypeId, usize>) -> f32 {
+        let have = army.get(&unit).copied().unwrap_or_default() as f32;
+        self.wanted(unit, army, 1) - have
+    }
+
+    /// how many of the unit the target asks for, if the shared part of the army had `extra` more
+    #[allow(clippy::cast_precision_loss)]
+    fn wanted(&self, unit: UnitTypeId, army: &HashMap<UnitTypeId, usize>, extra: usize) -> f32 {
+        let Some((_, quota)) = self.targets.iter().find(|(u, _)| *u == unit) else {
+            return 0.0;
+        };
+        match quota {
+            Quota::Count(count) => f32::from(*count),
+            Quota::Percent(percent) => {
+                let (mut shares, mut total) = (0.0, extra);
+                for (u, quota) in &self.targets {
+                    if let Quota::Percent(p) = quota {
+                        shares += f32::from(*p);
+                        total += army.get(u).copied().unwrap_or_default();
+                    }
+                }
+                f32::from(*percent) / shares * total as f32
+            }
+        }
+    }
+
+    /// where the army stands against the target, for the Army pane
+    pub fn gap(&self, army: &HashMap<UnitTypeId, usize>) -> Vec<Gap> {
+        self.targets
+            .iter()
+            .map(|(unit, _)| Gap {
+                unit: *unit,
+                have: army.get(unit).copied().unwrap_or_default(),
+                wanted: self.wanted(*unit, army, 0),
+            })
+            .collect()
+    }
+
+    /// Spreads the idle producers over the target by deficit, biggest first,
+    /// as far as the money and supply go. Nothing sits idle while there's a share it can make.
+    /// Something we can't afford right now is skipped rather than saved for,
+    /// so a missing 50 gas doesn't stop the zealots.
+    pub fn schedule<C>(&self, situation: &ProductionSituation, cost_of: C) -> Vec<ProductionOrder>
+    where
+        C: Fn(UnitTypeId) -> (Budget, u32),
+    {
+        let mut army = situation.army.clone();
+        let mut producers = situation.producers.clone();
+        let mut money = situation.money;
+        let mut supply_left = situation.supply_left;
+        let mut tech = situation.tech.clone();
+        if tech.contains(&UnitTypeId::WarpGate) {
+            tech.insert(UnitTypeId::Gateway);
+        }
+        let mut skipped = HashSet::new();
+        let mut orders = Vec::new();
+
+        loop {
+            let best = self
+                .targets
+                .iter()
+                .map(|(unit, _)| *unit)
+                .filter(|unit| !skipped.contains(unit))
+                .filter(|unit| unit_requires(*unit).iter().all(|s| tech.contains(s)))
+                .filter(|unit| {
+                    producers
+                        .iter()
+                        .any(|(_, kind)| train_ability(*kind, *unit).is_some())
+                })
+                .map(|unit| (unit, self.deficit(unit, &army)))
+                // counts stop once they're met, shares keep going with whatever is least over
+                .filter(|(unit, deficit)| *deficit > 0.0 || self.is_share(*unit))
+                // first in the target wins ties
+                .reduce(|best, next| if next.1 > best.1 { next } else { best });
+            let Some((unit, _)) = best else {
+                break;
+            };
+
+            let (price, supply) = cost_of(unit);
+            if !money.covers(price) || supply_left < supply {
+                skipped.insert(unit);
+                continue;
+            }
+            let Some(index) = producers
+                .iter()
+                .position(|(_, kind)| train_ability(*kind, unit).is_some())
+            else {
+                break;
+            };
+            let (producer, kind) = producers.remove(index);
+            if let Some(ability) = train_ability(kind, unit) {
+                orders.push(ProductionOrder {
+                    producer,
+                    unit,
+                    ability,
+                });
+            }
+            money.spend(price);
+            supply_left -= supply;
+            *army.entry(unit).or_default() += 1;
+        }
+        orders
+    }
+}
+
+/// one line of the composition gap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gap {
+    pub unit: UnitTypeId,
+    pub have: usize,
+    pub wanted: f32,
+}
+
+impl Display for Gap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}/{:.1}", self.unit, self.have, self.wanted)
+    }
+}
+
+/// What the scheduler gets to work with this step
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProductionSituation {
+    /// units we have or have started, by type
+    pub army: HashMap<UnitTypeId, usize>,
+    /// idle production that could start something now: tag and type
+    pub producers: Vec<(u64, UnitTypeId)>,
+    /// finished structures, for what's unlocked
+    pub tech: HashSet<UnitTypeId>,
+    pub money: Budget,
+    pub supply_left: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProductionOrder {
+    pub producer: u64,
+    pub unit: UnitTypeId,
+    pub ability: AbilityId,
+}
+
+/// the composition in the build right now
+#[derive(Debug, Clone, Default)]
+pub struct ProductionScheduler {
+    pub composition: Composition,
+}
+
+impl ProductionScheduler {
+    pub fn read_build(&mut self, active: &[BuildComponent]) {
+        self.composition = Composition::from_build(active);
+    }
+}
+
+impl ReBiCycler {
+    /// Starts units on every idle production structure the composition and `money` allow.
+    /// Warpgates warp their share in together, wherever `warp_in` puts them.
+    pub fn run_production(&mut self, money: &mut Budget) {
+        if self.production.composition.is_empty() {
+            return;
+        }
+        let situation = self.production_situation(*money);
+        let orders = self
+            .production
+            .composition
+            .schedule(&situation, |unit| self.unit_price(unit));
+
+        let mut warps: HashMap<(UnitTypeId, AbilityId), usize> = HashMap::new();
+        for order in orders {
+            money.spend(self.unit_price(order.unit).0);
+            let Some(producer) = self.units.my.structures.get(order.producer) else {
+                continue;
+            };
+            if producer.type_id() == UnitTypeId::WarpGate {
+                *warps.entry((order.unit, order.ability)).or_default() += 1;
+            } else {
+                producer.train(order.unit, false);
+            }
+        }
+        for ((unit, ability), count) in warps {
+            // warp_in pays for everything past the first out of what it's given
+            let (price, _) = self.unit_price(unit);
+            let mut extra = Budget::default();
+            for _ in 1..count {
+                extra.add(price);
+            }
+            if let Err(e) = self.warp_in(unit, ability, &mut extra) {
+                // nothing went out, so the first one's price comes back too
+                extra.add(price);
+                if !matches!(e, BuildError::AllBusy(_)) {
+                    self.log_error(format!("Couldn't warp in {unit:?}: {e:?}"));
+                }
+            }
+            money.add(extra);
+        }
+    }
+
+    fn unit_price(&self, unit: UnitTypeId) -> (Budget, u32) {
+        let cost = self.get_unit_cost(unit);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let supply = cost.supply as u32;
+        (Budget::new(cost.minerals, cost.vespene), supply)
+    }
+
+    fn production_situation(&self, money: Budget) -> ProductionSituation {
+        let structures = self.units.my.structures.ready();
+        let warp_gates: Vec<u64> = structures
+            .of_type(UnitTypeId::WarpGate)
+            .iter()
+            .map(Unit::tag)
+            .collect();
+        // warpgates go in the rounds warp_in would use
+        let mut producers: Vec<(u64, UnitTypeId)> = self
+            .warp_gates
+            .batch(&warp_gates, self.time)
+            .into_iter()
+            .map(|tag| (tag, UnitTypeId::WarpGate))
+            .collect();
+        producers.extend(
+            structures
+                .iter()
+                .filter(|s| s.type_id() != UnitTypeId::WarpGate)
+                .filter(|s| crate::is_protoss_production(&s.type_id()) && s.is_idle())
+                .map(|s| (s.tag(), s.type_id())),
+        );
+        let army = self
+            .production
+            .composition
+            .units()
+            .map(|unit| (unit, self.counter().all().count(unit)))
+            .collect();
+        ProductionSituation {
+            army,
+            producers,
+            tech: structures.iter().map(|s| s.type_id()).collect(),
+            money,
+            supply_left: self.supply_left,
+        }
+    }
+
+    /// The composition against the target, for the Army pane
+    pub fn composition_gap(&self) -> Vec<Gap> {
+        let situation = self.production_situation(Budget::default());
+        self.production.composition.gap(&situation.army)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(unit: UnitTypeId) -> (Budget, u32) {
+        match unit {
+            UnitTypeId::Zealot => (Budget::new(100, 0), 2),
+            UnitTypeId::Stalker => (Budget::new(125, 50), 2),
+            UnitTypeId::Immortal => (Budget::new(275, 100), 4),
+            _ => (Budget::new(50, 50), 1),
+        }
+    }
+
+    fn made(orders: &[ProductionOrder]) -> HashMap<UnitTypeId, usize> {
+        let mut counts = HashMap::new();
+        for order in orders {
+            *counts.entry(order.unit).or_default() += 1;
+        }
+        counts
+    }
+
+    fn gateway_army() -> Composition {
+        Composition::new(&[
+            (UnitTypeId::Zealot, Quota::Percent(50)),
+            (UnitTypeId::Stalker, Quota::Percent(30)),
+            (UnitTypeId::Immortal, Quota::Count(2)),
+        ])
+    }
+
+    #[test]
+    fn producers_go_where_the_gap_is() {
+        let situation = ProductionSituation {
+            // way short on stalkers
+            army: HashMap::from([(UnitTypeId::Zealot, 6), (UnitTypeId::Stalker, 1)]),
+            producers: vec![
+                (1, UnitTypeId::WarpGate),
+                (2, UnitTypeId::WarpGate),
+                (3, UnitTypeId::WarpGate),
+                (4, UnitTypeId::RoboticsFacility),
+            ],
+            tech: HashSet::from([
+                UnitTypeId::WarpGate,
+                UnitTypeId::CyberneticsCore,
+                UnitTypeId::RoboticsFacility,
+            ]),
+            money: Budget::new(2000, 1000),
+            supply_left: 40,
+        };
+        let orders = gateway_army().schedule(&situation, price);
+
+        assert_eq!(
+            made(&orders),
+            HashMap::from([(UnitTypeId::Immortal, 1), (UnitTypeId::Stalker, 3)])
+        );
+        let immortal = orders.iter().find(|o| o.unit == UnitTypeId::Immortal);
+        assert_eq!(
+            immortal.map(|o| (o.producer, o.ability)),
+            Some((4, AbilityId::RoboticsFacilityTrainImmortal))
+        );
+    }
+
+    #[test]
+    fn money_and_tech_hold_things_back() {
+        let situation = ProductionSituation {
+            army: HashMap::new(),
+            producers: vec![
+                (1, UnitTypeId::Gateway),
+                (2, UnitTypeId::Gateway),
+                (3, UnitTypeId::Gateway),
+            ],
+            // no core yet, so no stalkers
+            tech: HashSet::from([UnitTypeId::Gateway]),
+            money: Budget::new(250, 0),
+            supply_left: 10,
+        };
+        let orders = gateway_army().schedule(&situation, price);
+        assert_eq!(made(&orders), HashMap::from([(UnitTypeId::Zealot, 2)]));
+
+        let broke = ProductionSituation {
+            money: Budget::new(99, 0),
+            ..situation
+        };
+        assert!(gateway_army().schedule(&broke, price).is_empty());
+    }
+
+    #[test]
+    fn gap_shows_the_shares() {
+        let army = HashMap::from([
+            (UnitTypeId::Zealot, 4),
+            (UnitTypeId::Stalker, 4),
+            (UnitTypeId::Immortal, 1),
+        ]);
+        let lines: Vec<String> = gateway_army()
+            .gap(&army)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            vec!["Zealot: 4/5.0", "Stalker: 4/3.0", "Immortal: 1/2.0"]
+        );
+    }
+}
@@ -1,33 +1,54 @@
 use crate::army::ArmyController;
 use crate::assignment_manager::{Commands, Identity};
 use crate::build_order_definitions;
+use crate::build_orders::MapTarget;
+use crate::build_timeline::timeline_filename;
 use crate::build_tree::BuildOrderTree;
 use crate::chatter::{ChatAction, ChatController};
 use crate::construction::ConstructionManager;
 use crate::errors::BuildError;
 use crate::knowledge::Knowledge;
+use crate::map_analysis::MapAnalysis;
 use crate::mining::MinerController;
+use crate::nexus_energy::NexusEnergyManager;
+use crate::production::ProductionScheduler;
 use crate::readout::DisplayTerminal;
 use crate::siting::SitingDirector;
+use crate::strategy::{BuildResults, StrategySelector, RESULTS_FILE};
+use crate::supply::{is_blocked, SupplyBlockClock};
+use crate::walls::WallGap;
+use crate::warp_in::WarpGateTracker;
 use crate::Tag;
 
 use rust_sc2::prelude::*;
 
 const SURRENDER_DELAY_FRAMES: u32 = 200;
+/// the build file in `builds/` to play when there's nothing to choose from
+const DEFAULT_BUILD: &str = "nexus_first_two_base_charge";
 
 #[bot]
 #[derive(Default)]
 pub struct ReBiCycler {
     /// A tree data structure for our build orders
     pub build_order: BuildOrderTree,
-    /// information about how we want to place buildings. TODO use a grid
+    /// where we want to place buildings, on a grid of map tiles
     pub siting_director: SitingDirector,
     /// a place to store persistent knowledge about the game state
     pub knowledge: Knowledge,
+    /// ramps, chokes and our entrances, worked out once at the start
+    pub map_analysis: MapAnalysis,
     /// controls the army and assignments and stuff
     pub army_manager: ArmyController,
     /// manages workers and executes speed mining
     pub mining_manager: MinerController,
+    /// decides what nexus energy goes to
+    pub nexus_energy: NexusEnergyManager,
+    /// the army composition the build is after, and what's short of it
+    pub production: ProductionScheduler,
+    /// when each warpgate can warp in again
+    pub warp_gates: WarpGateTracker,
+    /// how long we've been stuck at max supply this game
+    pub supply_blocks: SupplyBlockClock,
     /// Manages construction projects
     pub construction_manager: ConstructionManager,
     /// Does chat stuff.
@@ -37,6 +58,10 @@ pub struct ReBiCycler {
     pub display_terminal: DisplayTerminal,
     game_started: bool,
     pub bot_state: BotState,
+    /// the build we picked this game, so the result goes to the right record
+    build_name: String,
+    /// who the results are kept against: the ladder's opponent id, or their race
+    opponent: String,
 }
 
 /// These are the methods that the game will call that the bot must implement. They are the entry points into all the code we want to run.
@@ -47,15 +72,53 @@ impl Player for ReBiCycler {
 
     /// called once at the start of the game, before the first frame
     fn on_start(&mut self) -> SC2Result<()> {
-        self.build_order = BuildOrderTree::nexus_first_two_base_charge().unwrap();
+        self.opponent = if self.opponent_id.is_empty() {
+            format!("{:?}", self.enemy_race)
+        } else {
+            self.opponent_id.clone()
+        };
+        self.build_name = self.choose_build();
+        self.build_order = match BuildOrderTree::from_name(&self.build_name) {
+            Ok(build) => build,
+            Err(e) => {
+                self.log_error(format!("Couldn't load build {}: {e}", self.build_name));
+                self.build_name = DEFAULT_BUILD.to_string();
+                BuildOrderTree::nexus_first_two_base_charge().unwrap()
+            }
+        };
+        match self.build_order.resolve_prerequisites() {
+            Ok(added) => {
+                for node in added.iter().filter_map(|n| self.build_order.get(*n)) {
+                    println!("Added {} to the build", node.name());
+                }
+            }
+            Err(e) => self.log_error(format!("Couldn't fill in the build's tech: {e:?}")),
+        }
+        for issue in self.build_order.validate() {
+            self.log_error(issue.to_string());
+        }
+        if self.enemy_race != Race::Random {
+            self.knowledge.confirm_race(self.enemy_race);
+        }
+
+        self.map_analysis = MapAnalysis::new(
+            &self.terrain_grids(),
+            self.start_location,
+            self.map_target(MapTarget::OurNatural),
+        );
+        if self.map_analysis.main_ramp().is_none() {
+            self.log_error("Couldn't find our main ramp".to_string());
+        }
 
         let map_center = self.game_info.map_center;
 
         self.siting_director.initialize_global_placement(
+            self.placement_grid(),
             self.expansions.clone().as_slice(),
             self.units.vespene_geysers.clone(),
             map_center,
         );
+        self.plan_walls(WallGap::against(self.enemy_race));
 
         self.game_started = true;
         self.do_chat(ChatAction::Greeting);
@@ -82,9 +145,16 @@ impl Player for ReBiCycler {
         }
         self.observe(frame_no);
 
+        self.supply_blocks
+            .tick(self.time, is_blocked(self.supply_used, self.supply_cap));
+        self.track_warp_ins();
+
         if frame_no % 50 == 0 {
-            self.step_build();
+            // pylons get paid for before the build spends the bank
             self.maintain_supply();
+            self.step_build();
+            self.update_army_states();
+            self.command_army();
             //self.map_worker_activity(frame_no);
         }
 
@@ -126,8 +196,25 @@ impl Player for ReBiCycler {
         Ok(())
     }
     /// called at the end of the game. maybe also call when surrendering
-    fn on_end(&self, _result: GameResult) -> SC2Result<()> {
+    fn on_end(&self, result: GameResult) -> SC2Result<()> {
+        if !matches!(result, GameResult::Undecided) {
+            if let Ok(mut results) = BuildResults::load(RESULTS_FILE) {
+                results.record(
+                    &self.opponent,
+                    &self.build_name,
+                    matches!(result, GameResult::Victory),
+                );
+                let _ = results.save(RESULTS_FILE);
+            }
+        }
         let _ = self.display_terminal.save_history("replays/history.txt");
+        let ended = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let _ = self
+            .build_order
+            .timeline
+            .save_csv(&timeline_filename(&self.build_name, ended));
         Ok(())
     }
 }
@@ -142,6 +229,25 @@ impl ReBiCycler {
             ..Default::default()
         }
     }
+    /// Picks a build from `builds/` by how each has done against this opponent
+    fn choose_build(&mut self) -> String {
+        let builds = BuildOrderTree::available();
+        let results = match BuildResults::load(RESULTS_FILE) {
+            Ok(results) => results,
+            Err(e) => {
+                self.log_error(format!("Couldn't read {RESULTS_FILE}: {e}"));
+                BuildResults::default()
+            }
+        };
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        StrategySelector::new(seed)
+            .choose(&builds, &self.opponent, &results)
+            .unwrap_or(DEFAULT_BUILD)
+            .to_string()
+    }
+
     /// Not everything is ready before the game starts, do stuff that we need everything ready for
     fn first_frame(&mut self) {
         let initial_nexus = self
@@ -175,6 +281,8 @@ impl ReBiCycler {
 
     fn unit_destroyed(&mut self, tag: u64, _alliance: Option<Alliance>) {
         let knowledge = self.knowledge.unit_destroyed(tag);
+        self.army_manager.remove_unit(tag);
+        self.warp_gates.remove(tag);
 
         if let Ok(unit_details) = knowledge {
             let unit_tag = Tag {
@@ -226,6 +334,8 @@ impl ReBiCycler {
         if let Some(unit) = self.units.my.units.get(unit_tag).cloned() {
             if unit.type_id() == UnitTypeId::Probe && self.game_started {
                 self.back_to_work(unit_tag);
+            } else if !unit.is_worker() && !unit.is_structure() {
+                self.join_fight(&unit);
             }
         } else {
             self.log_error(format!("UnitCreated but unit not found! {unit_tag}"));
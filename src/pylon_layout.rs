@@ -0,0 +1,224 @@
+use rust_sc2::prelude::*;
+
+use crate::{placement_grid::Tile, PYLON_POWER_RADIUS};
+
+const PYLON_TILES: usize = 2;
+const SLOT_TILES: usize = 3;
+/// how far the pylon can move off its anchor to fit more around it
+const PYLON_SEARCH: usize = 3;
+/// Slots go down in squares of four with a lane of one tile around them.
+/// Packing them solid powers more per pylon, but what comes out of the middle ones is stuck.
+const LANE_PERIOD: usize = 2 * SLOT_TILES + 1;
+
+/// How many pylons a block of slots hangs off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Power {
+    Single,
+    /// two side by side, every slot in reach of both, so losing one doesn't turn off the block
+    Redundant,
+}
+
+/// Pylons and the `Standard` slots they power, as the bottom left tiles of their footprints
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PowerBlock {
+    pub pylons: Vec<Tile>,
+    pub slots: Vec<Tile>,
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn footprint_center(corner: Tile, tiles: usize) -> Point2 {
+    let half = tiles as f32 / 2.0;
+    Point2::new(corner.0 as f32 + half, corner.1 as f32 + half)
+}
+
+const fn overlaps(a: Tile, a_tiles: usize, b: Tile, b_tiles: usize) -> bool {
+    a.0 < b.0 + b_tiles && b.0 < a.0 + a_tiles && a.1 < b.1 + b_tiles && b.1 < a.1 + a_tiles
+}
+
+/// whether a coordinate lands on the slot lattice starting at `offset`
+const fn on_lattice(value: usize, offset: usize) -> bool {
+    let step = (value + LANE_PERIOD - offset % LANE_PERIOD) % LANE_PERIOD;
+    step == 0 || step == SLOT_TILES
+}
+
+/// the pylon pairs, or single pylons, we'd try with the first one at `corner`
+fn pylon_sets(corner: Tile, power: Power) -> Vec<Vec<Tile>> {
+    match power {
+        Power::Single => vec![vec![corner]],
+        Power::Redundant => vec![
+            vec![corner, (corner.0 + PYLON_TILES, corner.1)],
+            vec![corner, (corner.0, corner.1 + PYLON_TILES)],
+        ],
+    }
+}
+
+/// Every lattice slot that fits and that all of the pylons reach
+fn pack(pylons: &[Tile], offset: Tile, fits: &impl Fn(Tile, usize) -> bool) -> Vec<Tile> {
+    let centers: Vec<Point2> = pylons
+        .iter()
+        .map(|p| footprint_center(*p, PYLON_TILES))
+        .collect();
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let reach = PYLON_POWER_RADIUS.ceil() as usize + SLOT_TILES;
+    let low = |axis: fn(&Tile) -> usize| {
+        pylons
+            .iter()
+            .map(axis)
+            .min()
+            .unwrap_or(0)
+            .saturating_sub(reach)
+    };
+    let high = |axis: fn(&Tile) -> usize| pylons.iter().map(axis).max().unwrap_or(0) + reach;
+    let xs = (low(|t| t.0)..=high(|t| t.0)).filter(|x| on_lattice(*x, offset.0));
+    let ys: Vec<usize> = (low(|t| t.1)..=high(|t| t.1))
+        .filter(|y| on_lattice(*y, offset.1))
+        .collect();
+
+    xs.flat_map(|x| ys.iter().map(move |y| (x, *y)))
+        .filter(|slot| {
+            let middle = footprint_center(*slot, SLOT_TILES);
+            centers
+                .iter()
+                .all(|pylon| pylon.distance(middle) <= PYLON_POWER_RADIUS)
+        })
+        .filter(|slot| {
+            pylons
+                .iter()
+                .all(|pylon| !overlaps(*slot, SLOT_TILES, *pylon, PYLON_TILES))
+        })
+        .filter(|slot| fits(*slot, SLOT_TILES))
+        .collect()
+}
+
+/// Picks pylon spots near `anchor`, and a lattice of `Standard` slots around them,
+/// so as many slots as possible are powered per pylon.
+/// `fits` says whether a square that many tiles wide could go down at a corner.
+/// Gives back nothing if there's no room for a pylon with anything powered around it.
+pub fn plan_block(
+    anchor: Point2,
+    power: Power,
+    fits: impl Fn(Tile, usize) -> bool,
+) -> Option<PowerBlock> {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let (x, y) = (
+        (anchor.x - 1.0).round().max(0.0) as usize,
+        (anchor.y - 1.0).round().max(0.0) as usize,
+    );
+    let corners = (x.saturating_sub(PYLON_SEARCH)..=x + PYLON_SEARCH)
+        .flat_map(|x| (y.saturating_sub(PYLON_SEARCH)..=y + PYLON_SEARCH).map(move |y| (x, y)));
+    let offsets: Vec<Tile> = (0..LANE_PERIOD)
+        .flat_map(|x| (0..LANE_PERIOD).map(move |y| (x, y)))
+        .collect();
+
+    let mut best: Option<(PowerBlock, f32)> = None;
+    for pylons in corners.flat_map(|corner| pylon_sets(corner, power)) {
+        if !pylons.iter().all(|pylon| fits(*pylon, PYLON_TILES)) {
+            continue;
+        }
+        let drift = footprint_center(pylons[0], PYLON_TILES).distance_squared(anchor);
+        for offset in &offsets {
+            let slots = pack(&pylons, *offset, &fits);
+            let better = best
+                .as_ref()
+                .map_or(!slots.is_empty(), |(block, best_drift)| {
+                    slots.len() > block.slots.len()
+                        || (slots.len() == block.slots.len() && drift < *best_drift)
+                });
+            if better {
+                let block = PowerBlock {
+                    pylons: pylons.clone(),
+                    slots,
+                };
+                best = Some((block, drift));
+            }
+        }
+    }
+    best.map(|(block, _)| block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::placement_grid::PlacementGrid;
+
+    fn claim(grid: &mut PlacementGrid, block: &PowerBlock) {
+        for pylon in &block.pylons {
+            assert!(grid.claim(*pylon, PYLON_TILES, true));
+        }
+        for slot in &block.slots {
+            assert!(grid.claim(*slot, SLOT_TILES, true));
+        }
+    }
+
+    #[test]
+    fn one_pylon_powers_a_full_block() {
+        let mut grid = PlacementGrid::open(40, 40);
+        let block = plan_block(Point2::new(20.0, 20.0), Power::Single, |corner, size| {
+            grid.fits(corner, size)
+        })
+        .unwrap();
+        assert_eq!(block.pylons.len(), 1);
+        // as many as `pylon_interceptor` gets, with room left to walk out
+        assert_eq!(block.slots.len(), 12, "{block:?}");
+        claim(&mut grid, &block);
+
+        // and everything in it can still get out to the edge of the map
+        let _ = grid.change_power(
+            footprint_center(block.pylons[0], PYLON_TILES),
+            PYLON_POWER_RADIUS,
+            true,
+        );
+        let in_the_way = |tile: Tile| grid.cell(tile).is_some_and(|c| c.site.is_some());
+        for slot in &block.slots {
+            assert!(grid.is_powered(footprint_center(*slot, SLOT_TILES)));
+            let door = grid.nearest_open(footprint_center(*slot, SLOT_TILES), 3, in_the_way);
+            assert!(door.is_some_and(|door| grid.connected(door, (0, 0), in_the_way)));
+        }
+    }
+
+    #[test]
+    fn losing_one_of_a_pair_keeps_the_lights_on() {
+        let mut grid = PlacementGrid::open(40, 40);
+        let block = plan_block(Point2::new(20.0, 20.0), Power::Redundant, |corner, size| {
+            grid.fits(corner, size)
+        })
+        .unwrap();
+        assert_eq!(block.pylons.len(), 2);
+        assert!(!block.slots.is_empty());
+        claim(&mut grid, &block);
+
+        let pylons: Vec<Point2> = block
+            .pylons
+            .iter()
+            .map(|p| footprint_center(*p, PYLON_TILES))
+            .collect();
+        for pylon in &pylons {
+            let _ = grid.change_power(*pylon, PYLON_POWER_RADIUS, true);
+        }
+        let _ = grid.change_power(pylons[0], PYLON_POWER_RADIUS, false);
+        for slot in &block.slots {
+            assert!(grid.is_powered(footprint_center(*slot, SLOT_TILES)));
+        }
+    }
+
+    #[test]
+    fn blocks_go_around_what_is_already_there() {
+        let mut grid = PlacementGrid::open(40, 40);
+        // a nexus right next to the anchor
+        assert!(grid.claim((14, 18), 5, true));
+        let block = plan_block(Point2::new(20.0, 20.0), Power::Single, |corner, size| {
+            grid.fits(corner, size)
+        })
+        .unwrap();
+        claim(&mut grid, &block);
+
+        // nothing fits in a corner with no room for slots
+        let cramped = PlacementGrid::open(4, 4);
+        assert_eq!(
+            plan_block(Point2::new(2.0, 2.0), Power::Single, |corner, size| {
+                cramped.fits(corner, size)
+            }),
+            None
+        );
+    }
+}
@@ -7,7 +7,10 @@ use std::{
 use crate::{
     errors::{BuildError, BuildingTransitionError, UnitEmploymentError},
     mining::Miner,
+    placement_grid::{PlacementGrid, Tile},
     protoss_bot::ReBiCycler,
+    pylon_layout::{plan_block, Power},
+    walls::WallPiece,
     Tag, PRISM_POWER_RADIUS, PYLON_POWER_RADIUS,
 };
 use image::Rgba;
@@ -15,6 +18,10 @@ use itertools::{iproduct, Either};
 use rust_sc2::{action::ActionResult, bot::Expansion, prelude::*};
 
 const NEARBY_RESOURCE_THRESHOLD: f32 = 12.0;
+/// how far from a point we'll look for open ground to start a path from
+const PATH_END_REACH: usize = 6;
+/// how far out from a nexus towards its minerals the workers walk
+const MINERAL_LINE_DEPTH: f32 = 4.5;
 
 #[allow(dead_code)]
 const EXPANSION_NAMES: [&str; 48] = [
@@ -50,6 +57,16 @@ impl BuildingStatus {
         matches!(self, Self::Built(_, _) | Self::Constructing(_, _))
     }
 
+    pub const fn is_powered(&self) -> bool {
+        matches!(
+            self,
+            Self::Blocked(_, PylonPower::Powered)
+                | Self::Free(_, PylonPower::Powered)
+                | Self::Built(_, PylonPower::Powered)
+                | Self::Constructing(_, PylonPower::Powered)
+        )
+    }
+
     pub const fn depower(self) -> Result<Self, BuildingTransitionError> {
         use PylonPower as P;
         match self {
@@ -139,6 +156,11 @@ impl ConstructionSite {
         matches!(self.location, LocationType::OnGeyser(_, _))
     }
 
+    /// a free site from the layout templates, which a wall can take over
+    pub const fn is_replaceable(&self) -> bool {
+        self.is_free() && !self.is_gas() && !matches!(self.size(), SlotSize::Townhall)
+    }
+
     pub const fn size(&self) -> SlotSize {
         match self.location {
             LocationType::AtPoint(_p, s) => s,
@@ -362,6 +384,33 @@ impl SlotSize {
         self.radius() * 2.0
     }
 
+    /// how many tiles wide the footprint is
+    pub const fn tiles(self) -> usize {
+        match self {
+            Self::Tumor => 1,
+            Self::Small => 2,
+            Self::Standard => 3,
+            Self::Townhall => 5,
+        }
+    }
+
+    /// The bottom left tile of a footprint centered near `center`.
+    /// Rounding here is what keeps a site from drifting half a tile.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn corner(self, center: Point2) -> Option<Tile> {
+        let (x, y) = (
+            (center.x - self.radius()).round(),
+            (center.y - self.radius()).round(),
+        );
+        (x >= 0.0 && y >= 0.0).then_some((x as usize, y as usize))
+    }
+
+    /// the exact middle of the footprint at `corner`
+    #[allow(clippy::cast_precision_loss)]
+    pub fn center(self, corner: Tile) -> Point2 {
+        Point2::new(corner.0 as f32, corner.1 as f32).offset(self.radius(), self.radius())
+    }
+
     const fn default_checker(self) -> UnitTypeId {
         match self {
             Self::Tumor => UnitTypeId::CreepTumor,
@@ -387,7 +436,20 @@ impl SlotSize {
 
 #[derive(Default)]
 pub struct SitingDirector {
-    sites: HashMap<Point2, ConstructionSite>,
+    /// which tiles are buildable, whose they are and whether they're powered
+    grid: PlacementGrid,
+    /// keyed by the bottom left tile of their footprint
+    sites: HashMap<Tile, ConstructionSite>,
+    path_checks: PathChecks,
+}
+
+/// What `would_block_path` said about each footprint, good until we build
+/// or lose something, or the links change
+#[derive(Default)]
+struct PathChecks {
+    built_on: Vec<Tile>,
+    links: Vec<(Point2, Point2)>,
+    answers: HashMap<(Tile, usize), bool>,
 }
 impl Debug for SitingDirector {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -424,34 +486,217 @@ impl Debug for SitingDirector {
 impl SitingDirector {
     pub fn initialize_global_placement(
         &mut self,
+        grid: PlacementGrid,
         expansions: &[Expansion],
         geysers: Units,
         map_center: Point2,
     ) {
-        let structures: Vec<(Point2, ConstructionSite)> = expansions
+        self.grid = grid;
+        let nexuses = expansions.iter().map(|e| ConstructionSite::nexus(e.loc));
+        let gasses = geysers.iter().map(ConstructionSite::new_gas);
+
+        // bases and gasses first, the power blocks get fitted around them
+        for site in nexuses.chain(gasses) {
+            self.add_site(site);
+        }
+        for (anchor, power) in expansions
             .iter()
-            .flat_map(|e| self.expansion_template(e.loc, e.center, map_center))
-            .map(|cs| (cs.location(), cs))
-            .collect();
+            .flat_map(|e| Self::block_anchors(e.loc, e.center, map_center))
+        {
+            self.add_power_block(anchor, power);
+        }
+    }
+
+    /// Lays out pylons and the slots they power around `anchor`,
+    /// going back to the fixed pattern if the optimizer can't fit anything
+    fn add_power_block(&mut self, anchor: Point2, power: Power) {
+        let grid = &self.grid;
+        let sites: Vec<ConstructionSite> =
+            match plan_block(anchor, power, |corner, size| grid.fits(corner, size)) {
+                Some(block) => {
+                    let pylons = block
+                        .pylons
+                        .iter()
+                        .map(|corner| ConstructionSite::pylon(SlotSize::Small.center(*corner)));
+                    let slots = block.slots.iter().map(|corner| {
+                        ConstructionSite::standard(SlotSize::Standard.center(*corner))
+                    });
+                    pylons.chain(slots).collect()
+                }
+                None => self.pylon_pattern(anchor.round()),
+            };
+        for site in sites {
+            self.add_site(site);
+        }
+    }
+
+    /// Snaps the site to the tiles under it and claims them.
+    /// Gives back false and drops the site if its footprint isn't all free, buildable ground.
+    pub fn add_site(&mut self, mut site: ConstructionSite) -> bool {
+        let size = site.size();
+        let Some(corner) = size.corner(site.location()) else {
+            return false;
+        };
+        // the placement grid has nowhere to put a nexus or an assimilator
+        let check_terrain = !site.is_gas() && size != SlotSize::Townhall;
+        if !self.grid.claim(corner, size.tiles(), check_terrain) {
+            return false;
+        }
+        if let LocationType::AtPoint(point, _) = &mut site.location {
+            *point = size.center(corner);
+        }
+        // pylons that are already up power it from the start
+        if self.grid.is_powered(site.location()) {
+            site.status = site.status.clone().repower();
+        }
+        self.sites.insert(corner, site);
+        true
+    }
+
+    /// Whether a wall piece could go here: buildable ground, only over sites
+    /// we haven't started on that aren't for a nexus or gas
+    pub fn could_wall(&self, corner: Tile, size: usize) -> bool {
+        self.grid.is_buildable(corner, size)
+            && self.grid.sites_under(corner, size).iter().all(|site| {
+                self.sites
+                    .get(site)
+                    .is_some_and(ConstructionSite::is_replaceable)
+            })
+    }
+
+    /// Registers a wall, clearing out the template sites it runs over.
+    /// Gives back how many of its pieces went in.
+    pub fn add_wall(&mut self, pieces: &[WallPiece]) -> usize {
+        let mut added = 0;
+        for piece in pieces {
+            let Ok(size) = SlotSize::from(piece.structure) else {
+                continue;
+            };
+            if !self.could_wall(piece.corner, piece.tiles) {
+                continue;
+            }
+            for corner in self.grid.sites_under(piece.corner, piece.tiles) {
+                if let Some(site) = self.sites.remove(&corner) {
+                    self.grid.release(corner, site.size().tiles());
+                }
+            }
+            let site = ConstructionSite::new(
+                Some(piece.structure),
+                LocationType::AtPoint(piece.center(), size),
+            );
+            if self.add_site(site) {
+                added += 1;
+            }
+        }
+        added
+    }
+
+    /// Whether a building of this size could go here without running into terrain or another site
+    pub fn can_place(&self, size: SlotSize, center: Point2) -> bool {
+        size.corner(center)
+            .is_some_and(|corner| self.grid.fits(corner, size.tiles()))
+    }
+
+    /// Whether a building of this size here would cut any of these pairs of points off from each other.
+    /// What we've built or started on is in the way too, the sites we've only planned aren't.
+    pub fn would_block_path(
+        &self,
+        size: SlotSize,
+        center: Point2,
+        links: &[(Point2, Point2)],
+    ) -> bool {
+        let Some(corner) = size.corner(center) else {
+            return false;
+        };
+        let tiles = size.tiles();
+        let built_on = |tile: Tile| {
+            self.grid
+                .cell(tile)
+                .and_then(|c| c.site)
+                .and_then(|site| self.sites.get(&site))
+                .is_some_and(ConstructionSite::is_mine)
+        };
+        let in_the_way = |tile: Tile| {
+            built_on(tile)
+                || ((corner.0..corner.0 + tiles).contains(&tile.0)
+                    && (corner.1..corner.1 + tiles).contains(&tile.1))
+        };
+
+        let linked = |from: Point2, to: Tile, blocked: &dyn Fn(Tile) -> bool| {
+            self.grid
+                .nearest_open(from, PATH_END_REACH, blocked)
+                .is_some_and(|from| self.grid.connected(from, to, blocked))
+        };
+        links.iter().any(|(from, to)| {
+            // the far end is found without the new building, so building right on it counts
+            let Some(to) = self.grid.nearest_open(*to, PATH_END_REACH, built_on) else {
+                return false;
+            };
+            // if it's already cut off, this building isn't what did it
+            linked(*from, to, &built_on) && !linked(*from, to, &in_the_way)
+        })
+    }
 
-        let gasses: Vec<(Point2, ConstructionSite)> = geysers
+    /// `would_block_path` for the site we're about to build on, remembered until
+    /// what we've built or the links change.
+    pub fn site_blocks_path(
+        &mut self,
+        size: SlotSize,
+        center: Point2,
+        links: &[(Point2, Point2)],
+    ) -> bool {
+        let Some(corner) = size.corner(center) else {
+            return false;
+        };
+        self.refresh_path_checks(links);
+        let key = (corner, size.tiles());
+        if let Some(answer) = self.path_checks.answers.get(&key) {
+            return *answer;
+        }
+        let answer = self.would_block_path(size, center, links);
+        self.path_checks.answers.insert(key, answer);
+        answer
+    }
+
+    /// forgets what `site_blocks_path` worked out if we've built or lost something since
+    pub fn refresh_path_checks(&mut self, links: &[(Point2, Point2)]) {
+        let mut built_on: Vec<Tile> = self
+            .sites
             .iter()
-            .map(ConstructionSite::new_gas)
-            .map(|cs| (cs.location(), cs))
+            .filter(|(_, site)| site.is_mine())
+            .map(|(corner, _)| *corner)
             .collect();
+        built_on.sort_unstable();
+        if built_on != self.path_checks.built_on || links != self.path_checks.links {
+            self.path_checks = PathChecks {
+                built_on,
+                links: links.to_vec(),
+                answers: HashMap::new(),
+            };
+        }
+    }
 
-        self.sites.extend(structures);
-        self.sites.extend(gasses);
+    /// whether `site_blocks_path` already turned this site down, without working it out again
+    pub fn known_to_block_path(&self, size: SlotSize, center: Point2) -> bool {
+        size.corner(center).is_some_and(|corner| {
+            self.path_checks.answers.get(&(corner, size.tiles())) == Some(&true)
+        })
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, Point2, ConstructionSite> {
-        self.sites.iter()
+    pub fn iter(&self) -> std::collections::hash_map::Values<'_, Tile, ConstructionSite> {
+        self.sites.values()
+    }
+
+    /// the site whose footprint covers this point
+    fn site_at_mut(&mut self, point: Point2) -> Option<&mut ConstructionSite> {
+        self.grid
+            .site_at(point)
+            .and_then(|corner| self.sites.get_mut(&corner))
     }
 
     pub fn add_initial_nexus(&mut self, nexus: &Unit) -> Result<(), BuildError> {
         let home_loc = self
-            .sites
-            .get_mut(&nexus.position())
+            .site_at_mut(nexus.position())
             .ok_or_else(|| BuildError::CantPlace(nexus.position(), nexus.type_id()))?;
         home_loc.status = BuildingStatus::Built(Tag::from_unit(nexus), PylonPower::Depowered);
         Ok(())
@@ -459,8 +704,7 @@ impl SitingDirector {
 
     pub fn add_assimilator(&mut self, building: &Unit) -> Result<(), BuildError> {
         let geyser = self
-            .sites
-            .get_mut(&building.position())
+            .site_at_mut(building.position())
             .ok_or_else(|| BuildError::NoConstructionSiteHere(building.position()))?;
         geyser.status = BuildingStatus::Built(Tag::from_unit(building), PylonPower::Depowered);
         Ok(())
@@ -496,7 +740,7 @@ impl SitingDirector {
             )))
         }?;
 
-        self.sites.get_mut(&location).map_or(
+        self.site_at_mut(location).map_or(
             Err(BuildError::NoConstructionSiteHere(location)),
             |spot| {
                 spot.transition(BuildingTransition::Construct(tag))
@@ -506,23 +750,10 @@ impl SitingDirector {
     }
 
     pub fn finish_construction(&mut self, structure: &Unit) -> Result<(), BuildError> {
-        if structure.is_geyser() {
-            self.sites
-                .get_mut(&structure.position())
-                .ok_or_else(|| {
-                    BuildError::NoConstructionSiteForFinishedBuilding(structure.type_id())
-                })?
-                .transition(BuildingTransition::Finish)
-                .map_err(BuildError::CantTransitionBuildingLocation)
-        } else {
-            self.sites
-                .get_mut(&structure.position())
-                .ok_or_else(|| {
-                    BuildError::NoConstructionSiteForFinishedBuilding(structure.type_id())
-                })?
-                .transition(BuildingTransition::Finish)
-                .map_err(BuildError::CantTransitionBuildingLocation)
-        }
+        self.site_at_mut(structure.position())
+            .ok_or_else(|| BuildError::NoConstructionSiteForFinishedBuilding(structure.type_id()))?
+            .transition(BuildingTransition::Finish)
+            .map_err(BuildError::CantTransitionBuildingLocation)
     }
 
     pub fn mark_position_blocked(
@@ -530,22 +761,47 @@ impl SitingDirector {
         location: Point2,
         make_obstructed: BuildingTransition,
     ) -> Result<(), Either<BuildError, BuildingTransitionError>> {
-        self.sites
-            .get_mut(&location)
+        self.site_at_mut(location)
             .ok_or(Either::Left(BuildError::NoConstructionSiteHere(location)))?
             .transition(make_obstructed)
             .map_err(|_| Either::Left(BuildError::NoConstructionSiteHere(location)))
     }
 
-    fn expansion_template(
-        &self,
+    /// Adds or takes away a power source and repowers or depowers the sites it reaches.
+    /// A site under two pylons stays powered when one of them dies.
+    pub fn change_power(
+        &mut self,
+        center: Point2,
+        radius: f32,
+        turned_on: bool,
+    ) -> Vec<BuildingTransitionError> {
+        if !self.grid.change_power(center, radius, turned_on) {
+            return Vec::new();
+        }
+        let grid = &self.grid;
+        self.sites
+            .values_mut()
+            .filter(|site| site.location().distance(center) <= radius)
+            .filter_map(|site| {
+                let powered = grid.is_powered(site.location());
+                if powered == site.status.is_powered() {
+                    None
+                } else if powered {
+                    site.transition(BuildingTransition::RePower).err()
+                } else {
+                    site.transition(BuildingTransition::DePower).err()
+                }
+            })
+            .collect()
+    }
+
+    /// Where a base's power blocks go: one tucked behind the minerals, where production is safest
+    /// so it gets a second pylon, and two off to the sides facing the map center
+    fn block_anchors(
         base_location: Point2,
         mineral_center: Point2,
-
         map_center: Point2,
-    ) -> Vec<ConstructionSite> {
-        let mut new_sites = Vec::new();
-
+    ) -> Vec<(Point2, Power)> {
         let distance_to_minerals = 6.0;
         let pylon_behind_location =
             { base_location.towards(mineral_center, distance_to_minerals + 3.0) };
@@ -555,19 +811,17 @@ impl SitingDirector {
         let pylon_spots =
             base_location.circle_intersection(direction_to_map_center, PYLON_DISTANCE_FROM_NEXUS);
 
-        let places = pylon_spots.map_or([Some(pylon_behind_location), None, None], |spots| {
-            [Some(pylon_behind_location), Some(spots[0]), Some(spots[1])]
-        });
-
-        let pylon_patterns: Vec<ConstructionSite> = places
-            .iter()
-            .flatten()
-            .flat_map(|p| self.pylon_pattern(p.round()))
-            .collect();
-
-        new_sites.extend(pylon_patterns);
-        new_sites.push(ConstructionSite::nexus(base_location));
-        new_sites
+        let behind = (pylon_behind_location.round(), Power::Redundant);
+        pylon_spots.map_or_else(
+            || vec![behind],
+            |spots| {
+                vec![
+                    behind,
+                    (spots[0].round(), Power::Single),
+                    (spots[1].round(), Power::Single),
+                ]
+            },
+        )
     }
 
     pub fn get_available_building_sites<'a>(
@@ -722,6 +976,7 @@ impl ReBiCycler {
     /// - `BuildError::NoPlacementLocation`
     /// - `BuildError::NoTrainer` if we have no workers
     /// - `BuildError::CantPlace` if we can't place at the found location.
+    /// - `BuildError::WouldBlockPath` if building there would wall off our main or a mineral line.
     pub fn build(&mut self, structure_type: UnitTypeId) -> Result<(), BuildError> {
         let size = SlotSize::from(structure_type)?;
         //self.game_data.units[structure_type]
@@ -733,20 +988,29 @@ impl ReBiCycler {
                     .distance(self.start_location)
                     .total_cmp(&b.location().distance(self.start_location))
             })
-            .ok_or(BuildError::NoPlacementLocations)?;
+            .ok_or(BuildError::NoPlacementLocations)?
+            .location();
+
+        let links = self.path_links();
+        if self
+            .siting_director
+            .site_blocks_path(size, position, &links)
+        {
+            return Err(BuildError::WouldBlockPath(position, structure_type));
+        }
 
         let builder = self
             .units
             .my
             .workers
-            .closest(position.location())
+            .closest(position)
             .ok_or(BuildError::NoTrainer)?
             .clone();
 
         let builder_tag = builder.tag();
         let builder_conscripted = self.mining_manager.remove_worker(builder_tag);
 
-        builder.build(structure_type, position.location(), false);
+        builder.build(structure_type, position, false);
         if builder_conscripted {
             self.log_error(format!(
                 "took builder {builder_tag} from mining to build {structure_type:?}"
@@ -766,6 +1030,33 @@ impl ReBiCycler {
         Err(BuildError::NoPlacementLocations)
     }
 
+    /// the terrain's buildable and pathable tiles, copied out for the siting director
+    pub fn placement_grid(&self) -> PlacementGrid {
+        let (width, height) = (self.game_info.map_size.x, self.game_info.map_size.y);
+        let cells: Vec<(usize, usize)> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .collect();
+        let buildable: Vec<bool> = cells.iter().map(|cell| self.is_placeable(*cell)).collect();
+        let pathable: Vec<bool> = cells.iter().map(|cell| self.is_pathable(*cell)).collect();
+        PlacementGrid::new(width, height, &buildable, &pathable)
+    }
+
+    /// What we can't afford to wall off: the main from the middle of the map,
+    /// and every nexus from the ground in front of its minerals.
+    pub fn path_links(&self) -> Vec<(Point2, Point2)> {
+        let out_of_the_main = (self.start_location, self.game_info.map_center);
+        let mineral_lines = self.units.my.townhalls.iter().filter_map(|nexus| {
+            let base = nexus.position();
+            self.expansions
+                .iter()
+                .find(|e| e.loc.distance(base) < 1.0)
+                .map(|e| (base, base.towards(e.center, MINERAL_LINE_DEPTH)))
+        });
+        std::iter::once(out_of_the_main)
+            .chain(mineral_lines)
+            .collect()
+    }
+
     pub fn update_building_power(
         &mut self,
         unit_change: UnitTypeId,
@@ -777,24 +1068,9 @@ impl ReBiCycler {
             UnitTypeId::WarpPrismPhasing | UnitTypeId::WarpPrism => PRISM_POWER_RADIUS,
             _ => 0.0,
         };
-        let change_type = if turned_on {
-            BuildingTransition::RePower
-        } else {
-            BuildingTransition::DePower
-        };
-
-        let errors: Vec<BuildingTransitionError> = self
+        let errors = self
             .siting_director
-            .sites
-            .values_mut()
-            .filter_map(|bl| {
-                if bl.location().distance(power_point) <= change_radius {
-                    bl.transition(change_type).err()
-                } else {
-                    None
-                }
-            })
-            .collect();
+            .change_power(power_point, change_radius, turned_on);
 
         for error in errors {
             println!("{error:?}");
@@ -804,17 +1080,30 @@ impl ReBiCycler {
     pub fn update_building_obstructions(
         &mut self,
     ) -> Vec<Either<BuildError, BuildingTransitionError>> {
+        let links = self.path_links();
+        self.siting_director.refresh_path_checks(&links);
         // a site is worth checking for this update if it's blocked or its free, constructing and built locations shouldn't be checked
-        let worth_checking = self.siting_director.sites.iter().filter_map(|(p, bl)| {
+        let worth_checking = self.siting_director.sites.values().filter_map(|bl| {
             bl.placement_checker()
-                .map(|checker| (p.to_owned(), checker))
+                .map(|checker| (bl.location(), bl.size(), checker))
         });
 
         // then we check if those locations are actually obstructed
+        // one we turned down for walling us in stays blocked until we build or lose something
         #[allow(clippy::needless_collect)]
         let changes: Vec<(Point2, BuildingTransition)> = worth_checking
             .into_iter()
-            .map(|(point, checker)| (point, self.location_is_obstructed(point, checker)))
+            .map(|(point, size, checker)| {
+                let transition = match self.location_is_obstructed(point, checker) {
+                    BuildingTransition::UnObstruct
+                        if self.siting_director.known_to_block_path(size, point) =>
+                    {
+                        BuildingTransition::Obstruct
+                    }
+                    transition => transition,
+                };
+                (point, transition)
+            })
             .collect();
 
         // then we update their status based on our findings
@@ -1019,4 +1308,159 @@ mod tests {
         touch_counts.retain(|_, v| v > &mut 1);
         assert!(touch_counts.is_empty(), "{touch_counts:?}");
     }
+
+    #[test]
+    fn sites_snap_to_tiles() {
+        let mut director = SitingDirector {
+            grid: PlacementGrid::open(40, 40),
+            ..SitingDirector::default()
+        };
+        for site in SitingDirector::pylon_interceptor(Point2::new(20.0, 20.0)) {
+            assert!(director.add_site(site));
+        }
+
+        // a hair off still lands on the same tiles, so it can't go down twice
+        assert!(director.add_site(ConstructionSite::standard(Point2::new(5.49, 5.52))));
+        assert!(!director.add_site(ConstructionSite::standard(Point2::new(5.5, 5.5))));
+        assert!(!director.can_place(SlotSize::Standard, Point2::new(7.5, 5.5)));
+        assert!(director.can_place(SlotSize::Standard, Point2::new(8.5, 5.5)));
+
+        let gateway = director.site_at_mut(Point2::new(6.9, 4.1)).unwrap();
+        assert_eq!(gateway.location(), Point2::new(5.5, 5.5));
+    }
+
+    #[test]
+    fn walls_take_over_template_sites() {
+        let mut director = SitingDirector {
+            grid: PlacementGrid::open(40, 40),
+            ..SitingDirector::default()
+        };
+        assert!(director.add_site(ConstructionSite::nexus(Point2::new(30.5, 30.5))));
+        assert!(director.add_site(ConstructionSite::standard(Point2::new(5.5, 5.5))));
+        let _ = director.change_power(Point2::new(8.0, 8.0), PYLON_POWER_RADIUS, true);
+
+        let wall = [
+            WallPiece {
+                structure: UnitTypeId::Gateway,
+                corner: (5, 5),
+                tiles: 3,
+            },
+            // the nexus stays put
+            WallPiece {
+                structure: UnitTypeId::CyberneticsCore,
+                corner: (29, 29),
+                tiles: 3,
+            },
+        ];
+        assert_eq!(director.add_wall(&wall), 1);
+        // and the gateway's spot is powered by the pylon that was already there
+        let gateway = director.site_at_mut(Point2::new(6.5, 6.5)).unwrap();
+        assert!(gateway.status.can_build(UnitTypeId::Gateway));
+        assert!(!gateway.status.can_build(UnitTypeId::Forge));
+    }
+
+    #[test]
+    fn buildings_that_close_the_ramp_are_refused() {
+        // a cliff down x = 25 with a ramp four tiles wide through it
+        let pathable: Vec<bool> = (0..40)
+            .flat_map(|y| (0..40).map(move |x| x != 25 || (18..22).contains(&y)))
+            .collect();
+        let mut director = SitingDirector {
+            grid: PlacementGrid::new(40, 40, &[true; 1600], &pathable),
+            ..SitingDirector::default()
+        };
+        let main_to_middle = [(Point2::new(10.5, 20.5), Point2::new(35.5, 20.5))];
+
+        // one gateway half across the ramp still leaves room
+        assert!(director.add_site(ConstructionSite::standard(Point2::new(25.5, 19.5))));
+        assert!(!director.would_block_path(
+            SlotSize::Small,
+            Point2::new(25.0, 22.0),
+            &main_to_middle
+        ));
+        // but once it's up, a pylon in the last gap walls us in
+        director
+            .site_at_mut(Point2::new(25.5, 19.5))
+            .unwrap()
+            .status = BuildingStatus::Built(
+            Tag {
+                tag: 1,
+                unit_type: UnitTypeId::Gateway,
+            },
+            PylonPower::Powered,
+        );
+        assert!(director.would_block_path(
+            SlotSize::Small,
+            Point2::new(25.0, 22.0),
+            &main_to_middle
+        ));
+        assert!(!director.would_block_path(
+            SlotSize::Small,
+            Point2::new(15.0, 30.0),
+            &main_to_middle
+        ));
+        // and nothing goes right where the workers need to stand
+        assert!(director.would_block_path(
+            SlotSize::Small,
+            Point2::new(35.0, 21.0),
+            &main_to_middle
+        ));
+    }
+
+    #[test]
+    fn wall_pieces_still_keep_the_way_out() {
+        // the same cliff and ramp, with a gateway and a battery planned across it
+        let pathable: Vec<bool> = (0..40)
+            .flat_map(|y| (0..40).map(move |x| x != 25 || (18..22).contains(&y)))
+            .collect();
+        let mut director = SitingDirector {
+            grid: PlacementGrid::new(40, 40, &[true; 1600], &pathable),
+            ..SitingDirector::default()
+        };
+        let wall = [
+            WallPiece {
+                structure: UnitTypeId::Gateway,
+                corner: (24, 17),
+                tiles: 3,
+            },
+            WallPiece {
+                structure: UnitTypeId::ShieldBattery,
+                corner: (24, 20),
+                tiles: 2,
+            },
+        ];
+        assert_eq!(director.add_wall(&wall), 2);
+        director
+            .site_at_mut(Point2::new(25.5, 18.5))
+            .unwrap()
+            .status = BuildingStatus::Built(
+            Tag {
+                tag: 1,
+                unit_type: UnitTypeId::Gateway,
+            },
+            PylonPower::Powered,
+        );
+        let main_to_middle = [(Point2::new(10.5, 20.5), Point2::new(35.5, 20.5))];
+        let battery = Point2::new(25.0, 21.0);
+
+        // the battery would shut the only way out of the main, planned or not
+        assert!(director.site_blocks_path(SlotSize::Small, battery, &main_to_middle));
+        // and asking twice gets the same answer
+        for _ in 0..2 {
+            assert!(director.site_blocks_path(
+                SlotSize::Small,
+                Point2::new(35.0, 21.0),
+                &main_to_middle
+            ));
+            assert!(!director.site_blocks_path(
+                SlotSize::Small,
+                Point2::new(15.0, 30.0),
+                &main_to_middle
+            ));
+        }
+        // so the ones we turned down can stay blocked without another look
+        assert!(director.known_to_block_path(SlotSize::Small, Point2::new(35.0, 21.0)));
+        assert!(!director.known_to_block_path(SlotSize::Small, Point2::new(15.0, 30.0)));
+        assert!(director.known_to_block_path(SlotSize::Small, battery));
+    }
 }
@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, Write},
+};
+
+/// Where we keep results between games, next to the history
+pub const RESULTS_FILE: &str = "replays/build_results.txt";
+
+/// How a build has done against one opponent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildRecord {
+    pub wins: u32,
+    pub games: u32,
+}
+
+/// Wins and games for every build we've played, per opponent.
+/// Stored one `opponent<TAB>build<TAB>wins<TAB>games` line each.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildResults {
+    records: HashMap<(String, String), BuildRecord>,
+}
+
+impl BuildResults {
+    /// Reads the results file. No file yet means no games yet.
+    /// # Errors
+    /// Any io error other than the file not existing.
+    pub fn load(filename: &str) -> io::Result<Self> {
+        match fs::read_to_string(filename) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// lines that don't make sense are skipped, losing one record beats losing them all
+    pub fn parse(text: &str) -> Self {
+        let mut results = Self::default();
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [opponent, build, wins, games] = fields[..] else {
+                continue;
+            };
+            let (Ok(wins), Ok(games)) = (wins.parse(), games.parse()) else {
+                continue;
+            };
+            results.records.insert(
+                (opponent.to_string(), build.to_string()),
+                BuildRecord { wins, games },
+            );
+        }
+        results
+    }
+
+    /// sorted, so the file doesn't churn between games
+    pub fn to_text(&self) -> String {
+        let mut lines: Vec<_> = self.records.iter().collect();
+        lines.sort_by(|a, b| a.0.cmp(b.0));
+        let mut out = String::new();
+        for ((opponent, build), record) in lines {
+            let _ = writeln!(
+                out,
+                "{opponent}\t{build}\t{}\t{}",
+                record.wins, record.games
+            );
+        }
+        out
+    }
+
+    /// # Errors
+    /// Any io error from writing the file.
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut output = File::create(filename)?;
+        write!(output, "{}", self.to_text())
+    }
+
+    pub fn record(&mut self, opponent: &str, build: &str, won: bool) {
+        let record = self
+            .records
+            .entry((opponent.to_string(), build.to_string()))
+            .or_default();
+        record.games += 1;
+        if won {
+            record.wins += 1;
+        }
+    }
+
+    pub fn get(&self, opponent: &str, build: &str) -> BuildRecord {
+        self.records
+            .get(&(opponent.to_string(), build.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Picks which build to play with UCB1 over each build's results against this opponent.
+/// Builds we haven't tried against them go first. Ties are broken by a seeded
+/// generator, so the same seed and results always give the same pick.
+#[derive(Debug, Clone)]
+pub struct StrategySelector {
+    state: u64,
+}
+
+impl StrategySelector {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn choose<'a>(
+        &mut self,
+        builds: &'a [String],
+        opponent: &str,
+        results: &BuildResults,
+    ) -> Option<&'a str> {
+        let records: Vec<BuildRecord> = builds.iter().map(|b| results.get(opponent, b)).collect();
+
+        let untried: Vec<usize> = (0..builds.len())
+            .filter(|i| records[*i].games == 0)
+            .collect();
+        if !untried.is_empty() {
+            return Some(&builds[untried[self.pick(untried.len())]]);
+        }
+
+        let scores: Vec<f64> = records.iter().map(|r| ucb1(*r, &records)).collect();
+        let best = scores.iter().copied().max_by(f64::total_cmp)?;
+        let tied: Vec<usize> = (0..builds.len())
+            .filter(|i| (scores[*i] - best).abs() < f64::EPSILON)
+            .collect();
+        Some(&builds[tied[self.pick(tied.len())]])
+    }
+
+    /// splitmix64, plenty for picking between a handful of builds
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn pick(&mut self, count: usize) -> usize {
+        (self.next_u64() % count as u64) as usize
+    }
+}
+
+/// win rate plus an exploration bonus that shrinks the more a build is played
+fn ucb1(record: BuildRecord, all: &[BuildRecord]) -> f64 {
+    let total: u32 = all.iter().map(|r| r.games).sum();
+    let games = f64::from(record.games);
+    f64::from(record.wins) / games + (2.0 * f64::from(total).ln() / games).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builds() -> Vec<String> {
+        vec!["gate_expand".to_string(), "nexus_first".to_string()]
+    }
+
+    #[test]
+    fn results_round_trip() {
+        let mut results = BuildResults::default();
+        results.record("bob", "nexus_first", true);
+        results.record("bob", "nexus_first", false);
+        results.record("Zerg", "gate_expand", false);
+
+        let text = results.to_text();
+        assert_eq!(
+            text,
+            "Zerg\tgate_expand\t0\t1\nbob\tnexus_first\t1\t2\n".to_string()
+        );
+        assert_eq!(BuildResults::parse(&(text + "garbage\n")), results);
+        assert_eq!(
+            results.get("bob", "nexus_first"),
+            BuildRecord { wins: 1, games: 2 }
+        );
+        assert_eq!(results.get("bob", "gate_expand"), BuildRecord::default());
+    }
+
+    #[test]
+    fn same_seed_same_pick() {
+        let builds = builds();
+        let results = BuildResults::default();
+        let picks = |seed| {
+            let mut selector = StrategySelector::new(seed);
+            (0..10)
+                .map(|_| selector.choose(&builds, "bob", &results))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(picks(7), picks(7));
+        // both get tried eventually
+        assert!(picks(7).contains(&Some("gate_expand")));
+        assert!(picks(7).contains(&Some("nexus_first")));
+    }
+
+    #[test]
+    fn stops_repeating_a_losing_opener() {
+        let builds = builds();
+        let mut results = BuildResults::default();
+        for _ in 0..5 {
+            results.record("bob", "nexus_first", false);
+        }
+        let mut selector = StrategySelector::new(1);
+        // never tried the other one against bob
+        assert_eq!(
+            selector.choose(&builds, "bob", &results),
+            Some("gate_expand")
+        );
+
+        for _ in 0..3 {
+            results.record("bob", "gate_expand", true);
+        }
+        for seed in 0..20 {
+            let mut selector = StrategySelector::new(seed);
+            assert_eq!(
+                selector.choose(&builds, "bob", &results),
+                Some("gate_expand")
+            );
+        }
+        // other opponents have their own records
+        assert!(StrategySelector::new(1)
+            .choose(&builds, "alice", &results)
+            .is_some());
+        assert_eq!(StrategySelector::new(1).choose(&[], "bob", &results), None);
+    }
+}
@@ -0,0 +1,217 @@
+pub const PYLON_SUPPLY: u32 = 8;
+pub const NEXUS_SUPPLY: u32 = 15;
+pub const MAX_SUPPLY: u32 = 200;
+pub const PYLON_BUILD_SECONDS: f32 = 18.0;
+/// a probe has to get to the spot before the pylon starts
+const PYLON_START_DELAY: f32 = 4.0;
+/// how far ahead to look: anything we need further out can wait for the next pylon
+pub const FORECAST_SECONDS: f32 = PYLON_BUILD_SECONDS + PYLON_START_DELAY;
+pub const CHRONO_SPEEDUP: f32 = 1.5;
+pub const CHRONO_SECONDS: f32 = 20.0;
+
+/// Something that's going to keep taking supply: a structure partway through making a unit,
+/// an idle one that could start any moment, or a warpgate waiting on its cooldown.
+/// The unit it's on now is already in supply used, it's the next ones that count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Producer {
+    supply: u32,
+    /// seconds of work left before it can start the next one, at normal speed
+    remaining: f32,
+    /// seconds of work per unit after that
+    cycle: f32,
+    /// seconds of chrono it has left
+    chrono: f32,
+}
+
+impl Producer {
+    pub const fn new(supply: u32, remaining: f32, cycle: f32, chrono: f32) -> Self {
+        Self {
+            supply,
+            remaining,
+            cycle,
+            chrono,
+        }
+    }
+
+    /// sitting there with nothing to do, it'll start one when the build gets to it
+    pub const fn idle(supply: u32) -> Self {
+        Self::new(supply, 0.0, f32::INFINITY, 0.0)
+    }
+
+    /// Supply it'll have started on within `seconds`, assuming it keeps making the same thing
+    pub fn supply_within(&self, seconds: f32) -> u32 {
+        let mut clock = 0.0;
+        let mut chrono = self.chrono;
+        let mut work = self.remaining;
+        let mut supply = 0;
+        loop {
+            let (took, chrono_left) = real_seconds(work, chrono);
+            clock += took;
+            chrono = chrono_left;
+            if clock > seconds {
+                return supply;
+            }
+            supply += self.supply;
+            if !self.cycle.is_finite() {
+                return supply;
+            }
+            work = self.cycle;
+        }
+    }
+}
+
+/// How long `work` seconds of production takes with `chrono` seconds of boost left,
+/// and how much boost is left after
+fn real_seconds(work: f32, chrono: f32) -> (f32, f32) {
+    let boosted_work = chrono * CHRONO_SPEEDUP;
+    if work <= boosted_work {
+        let took = work / CHRONO_SPEEDUP;
+        (took, chrono - took)
+    } else {
+        (chrono + work - boosted_work, 0.0)
+    }
+}
+
+/// Supply now, what's coming, and what's going to be made, for deciding on pylons
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SupplyForecast {
+    pub used: u32,
+    pub cap: u32,
+    pub producers: Vec<Producer>,
+    /// pylons and nexuses under way: seconds until they finish, and how much they'll give
+    pub incoming: Vec<(f32, u32)>,
+}
+
+impl SupplyForecast {
+    pub fn projected_use(&self, seconds: f32) -> u32 {
+        let more: u32 = self
+            .producers
+            .iter()
+            .map(|p| p.supply_within(seconds))
+            .sum();
+        (self.used + more).min(MAX_SUPPLY)
+    }
+
+    pub fn projected_cap(&self, seconds: f32) -> u32 {
+        let more: u32 = self
+            .incoming
+            .iter()
+            .filter(|(ready_in, _)| *ready_in <= seconds)
+            .map(|(_, supply)| supply)
+            .sum();
+        (self.cap + more).min(MAX_SUPPLY)
+    }
+
+    /// How many pylons to start now so production doesn't stall by the time they're done.
+    /// Sitting right at the cap counts as stalled, the next unit won't fit.
+    pub fn pylons_needed(&self) -> u32 {
+        let cap = self.projected_cap(FORECAST_SECONDS);
+        if cap == MAX_SUPPLY {
+            return 0;
+        }
+        let short = (self.projected_use(FORECAST_SECONDS) + 1).saturating_sub(cap);
+        short.div_ceil(PYLON_SUPPLY)
+    }
+}
+
+/// Adds up how long we've spent without room for another unit
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SupplyBlockClock {
+    seconds: f32,
+    blocked_since: Option<f32>,
+}
+
+impl SupplyBlockClock {
+    pub fn tick(&mut self, now: f32, blocked: bool) {
+        if let Some(since) = self.blocked_since.take() {
+            self.seconds += now - since;
+        }
+        if blocked {
+            self.blocked_since = Some(now);
+        }
+    }
+
+    pub const fn seconds(&self) -> f32 {
+        self.seconds
+    }
+}
+
+/// no room for anything, and more pylons would help
+pub const fn is_blocked(used: u32, cap: u32) -> bool {
+    cap < MAX_SUPPLY && used >= cap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chrono_brings_the_next_unit_forward() {
+        // a gateway 10 seconds from done, then a 27 second zealot cycle
+        let plain = Producer::new(2, 10.0, 27.0, 0.0);
+        assert_eq!(plain.supply_within(9.0), 0);
+        assert_eq!(plain.supply_within(11.0), 2);
+        assert_eq!(plain.supply_within(40.0), 4);
+
+        let boosted = Producer::new(2, 10.0, 27.0, 20.0);
+        assert_eq!(boosted.supply_within(7.0), 2);
+        assert_eq!(boosted.supply_within(30.0), 4);
+
+        assert_eq!(Producer::idle(1).supply_within(0.0), 1);
+        assert_eq!(Producer::idle(1).supply_within(100.0), 1);
+    }
+
+    #[test]
+    fn pylons_go_down_before_the_block() {
+        let mut forecast = SupplyForecast {
+            used: 20,
+            cap: 23,
+            producers: vec![
+                Producer::new(1, 5.0, 12.0, 0.0),
+                Producer::new(2, 15.0, 27.0, 0.0),
+                Producer::idle(2),
+            ],
+            incoming: vec![],
+        };
+        // 20 now, two probes, a zealot and whatever the idle gate makes is 26
+        assert_eq!(forecast.projected_use(FORECAST_SECONDS), 26);
+        assert_eq!(forecast.pylons_needed(), 1);
+
+        // a nexus that's nearly done covers it, one that's just started doesn't
+        forecast.incoming.push((60.0, NEXUS_SUPPLY));
+        assert_eq!(forecast.pylons_needed(), 1);
+        forecast.incoming.push((10.0, NEXUS_SUPPLY));
+        assert_eq!(forecast.pylons_needed(), 0);
+
+        let maxed = SupplyForecast {
+            used: 198,
+            cap: 200,
+            producers: vec![Producer::idle(2); 10],
+            incoming: vec![],
+        };
+        assert_eq!(maxed.pylons_needed(), 0);
+
+        // landing exactly on the cap is still a block
+        let exact = SupplyForecast {
+            used: 14,
+            cap: 15,
+            producers: vec![Producer::idle(1)],
+            incoming: vec![],
+        };
+        assert_eq!(exact.pylons_needed(), 1);
+    }
+
+    #[test]
+    fn block_clock_counts_blocked_time() {
+        let mut clock = SupplyBlockClock::default();
+        clock.tick(10.0, false);
+        clock.tick(12.0, true);
+        clock.tick(15.0, true);
+        clock.tick(16.5, false);
+        clock.tick(30.0, false);
+        assert!((clock.seconds() - 4.5).abs() < f32::EPSILON);
+        assert!(is_blocked(23, 23));
+        assert!(!is_blocked(22, 23));
+        assert!(!is_blocked(200, 200));
+    }
+}
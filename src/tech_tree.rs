@@ -0,0 +1,488 @@
+use std::{collections::HashSet, fmt::Display};
+
+use rust_sc2::ids::{AbilityId, UnitTypeId, UpgradeId};
+
+use crate::{
+    build_orders::{BuildCondition, BuildOrderAction},
+    build_tree::{BuildComponent, BuildOrderTree, ConditionGroup, ConditionOperator, TreeError},
+};
+
+/// Something that has to be in place before parts of a build can happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tech {
+    Structure(UnitTypeId),
+    Upgrade(UpgradeId),
+}
+
+impl Display for Tech {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Structure(structure) => write!(f, "{structure:?}"),
+            Self::Upgrade(upgrade) => write!(f, "{upgrade:?}"),
+        }
+    }
+}
+
+/// The structures that have to exist to make this unit: where it comes from and what unlocks it.
+/// Warpgates count as gateways here, they need one to exist first anyway.
+#[must_use]
+pub const fn unit_requires(unit: UnitTypeId) -> &'static [UnitTypeId] {
+    match unit {
+        UnitTypeId::Probe => &[UnitTypeId::Nexus],
+        UnitTypeId::Zealot => &[UnitTypeId::Gateway],
+        UnitTypeId::Stalker | UnitTypeId::Sentry | UnitTypeId::Adept => {
+            &[UnitTypeId::Gateway, UnitTypeId::CyberneticsCore]
+        }
+        UnitTypeId::HighTemplar => &[UnitTypeId::Gateway, UnitTypeId::TemplarArchive],
+        UnitTypeId::DarkTemplar => &[UnitTypeId::Gateway, UnitTypeId::DarkShrine],
+        UnitTypeId::Observer | UnitTypeId::WarpPrism | UnitTypeId::Immortal => {
+            &[UnitTypeId::RoboticsFacility]
+        }
+        UnitTypeId::Colossus | UnitTypeId::Disruptor => {
+            &[UnitTypeId::RoboticsFacility, UnitTypeId::RoboticsBay]
+        }
+        UnitTypeId::Phoenix | UnitTypeId::Oracle | UnitTypeId::VoidRay => &[UnitTypeId::Stargate],
+        UnitTypeId::Tempest | UnitTypeId::Carrier => {
+            &[UnitTypeId::Stargate, UnitTypeId::FleetBeacon]
+        }
+        UnitTypeId::Mothership => &[UnitTypeId::Nexus, UnitTypeId::FleetBeacon],
+        _ => &[],
+    }
+}
+
+/// Where an upgrade is researched and the ability that does it
+#[must_use]
+pub const fn researched_at(upgrade: UpgradeId) -> Option<(AbilityId, UnitTypeId)> {
+    let research = match upgrade {
+        UpgradeId::WarpGateResearch => (AbilityId::ResearchWarpGate, UnitTypeId::CyberneticsCore),
+        UpgradeId::Charge => (AbilityId::ResearchCharge, UnitTypeId::TwilightCouncil),
+        UpgradeId::BlinkTech => (AbilityId::ResearchBlink, UnitTypeId::TwilightCouncil),
+        UpgradeId::AdeptPiercingAttack => (
+            AbilityId::ResearchAdeptResonatingGlaives,
+            UnitTypeId::TwilightCouncil,
+        ),
+        UpgradeId::PsiStormTech => (AbilityId::ResearchPsiStorm, UnitTypeId::TemplarArchive),
+        UpgradeId::DarkTemplarBlinkUpgrade => {
+            (AbilityId::ResearchShadowStrike, UnitTypeId::DarkShrine)
+        }
+        UpgradeId::ExtendedThermalLance => (
+            AbilityId::ResearchExtendedThermalLance,
+            UnitTypeId::RoboticsBay,
+        ),
+        UpgradeId::ObserverGraviticBooster => {
+            (AbilityId::ResearchGraviticBooster, UnitTypeId::RoboticsBay)
+        }
+        UpgradeId::GraviticDrive => (AbilityId::ResearchGraviticDrive, UnitTypeId::RoboticsBay),
+        UpgradeId::ProtossGroundWeaponsLevel1 => (
+            AbilityId::ForgeResearchProtossGroundWeaponsLevel1,
+            UnitTypeId::Forge,
+        ),
+        UpgradeId::ProtossGroundWeaponsLevel2 => (
+            AbilityId::ForgeResearchProtossGroundWeaponsLevel2,
+            UnitTypeId::Forge,
+        ),
+        UpgradeId::ProtossGroundWeaponsLevel3 => (
+            AbilityId::ForgeResearchProtossGroundWeaponsLevel3,
+            UnitTypeId::Forge,
+        ),
+        UpgradeId::ProtossGroundArmorsLevel1 => (
+            AbilityId::ForgeResearchProtossGroundArmorLevel1,
+            UnitTypeId::Forge,
+        ),
+        UpgradeId::ProtossGroundArmorsLevel2 => (
+            AbilityId::ForgeResearchProtossGroundArmorLevel2,
+            UnitTypeId::Forge,
+        ),
+        UpgradeId::ProtossGroundArmorsLevel3 => (
+            AbilityId::ForgeResearchProtossGroundArmorLevel3,
+            UnitTypeId::Forge,
+        ),
+        UpgradeId::ProtossShieldsLevel1 => (
+            AbilityId::ForgeResearchProtossShieldsLevel1,
+            UnitTypeId::Forge,
+        ),
+        UpgradeId::ProtossShieldsLevel2 => (
+            AbilityId::ForgeResearchProtossShieldsLevel2,
+            UnitTypeId::Forge,
+        ),
+        UpgradeId::ProtossShieldsLevel3 => (
+            AbilityId::ForgeResearchProtossShieldsLevel3,
+            UnitTypeId::Forge,
+        ),
+        UpgradeId::ProtossAirWeaponsLevel1 => (
+            AbilityId::CyberneticsCoreResearchProtossAirWeaponsLevel1,
+            UnitTypeId::CyberneticsCore,
+        ),
+        UpgradeId::ProtossAirWeaponsLevel2 => (
+            AbilityId::CyberneticsCoreResearchProtossAirWeaponsLevel2,
+            UnitTypeId::CyberneticsCore,
+        ),
+        UpgradeId::ProtossAirWeaponsLevel3 => (
+            AbilityId::CyberneticsCoreResearchProtossAirWeaponsLevel3,
+            UnitTypeId::CyberneticsCore,
+        ),
+        UpgradeId::ProtossAirArmorsLevel1 => (
+            AbilityId::CyberneticsCoreResearchProtossAirArmorsLevel1,
+            UnitTypeId::CyberneticsCore,
+        ),
+        UpgradeId::ProtossAirArmorsLevel2 => (
+            AbilityId::CyberneticsCoreResearchProtossAirArmorsLevel2,
+            UnitTypeId::CyberneticsCore,
+        ),
+        UpgradeId::ProtossAirArmorsLevel3 => (
+            AbilityId::CyberneticsCoreResearchProtossAirArmorsLevel3,
+            UnitTypeId::CyberneticsCore,
+        ),
+        _ => return None,
+    };
+    Some(research)
+}
+
+/// What an upgrade needs besides its researcher: the level before it,
+/// and the structure that unlocks the higher levels
+#[must_use]
+pub const fn upgrade_requires(upgrade: UpgradeId) -> (Option<UpgradeId>, Option<UnitTypeId>) {
+    use UpgradeId as U;
+    let twilight = Some(UnitTypeId::TwilightCouncil);
+    let beacon = Some(UnitTypeId::FleetBeacon);
+    match upgrade {
+        U::ProtossGroundWeaponsLevel2 => (Some(U::ProtossGroundWeaponsLevel1), twilight),
+        U::ProtossGroundWeaponsLevel3 => (Some(U::ProtossGroundWeaponsLevel2), twilight),
+        U::ProtossGroundArmorsLevel2 => (Some(U::ProtossGroundArmorsLevel1), twilight),
+        U::ProtossGroundArmorsLevel3 => (Some(U::ProtossGroundArmorsLevel2), twilight),
+        U::ProtossShieldsLevel2 => (Some(U::ProtossShieldsLevel1), twilight),
+        U::ProtossShieldsLevel3 => (Some(U::ProtossShieldsLevel2), twilight),
+        U::ProtossAirWeaponsLevel2 => (Some(U::ProtossAirWeaponsLevel1), beacon),
+        U::ProtossAirWeaponsLevel3 => (Some(U::ProtossAirWeaponsLevel2), beacon),
+        U::ProtossAirArmorsLevel2 => (Some(U::ProtossAirArmorsLevel1), beacon),
+        U::ProtossAirArmorsLevel3 => (Some(U::ProtossAirArmorsLevel2), beacon),
+        _ => (None, None),
+    }
+}
+
+/// The tech that has to be there right before this one, not counting what that needs in turn
+fn tech_requires(tech: Tech) -> Vec<Tech> {
+    match tech {
+        Tech::Structure(structure) => crate::structure_prerequisite(structure)
+            .map(Tech::Structure)
+            .into_iter()
+            .collect(),
+        Tech::Upgrade(upgrade) => {
+            let researcher = researched_at(upgrade).map(|(_, researcher)| researcher);
+            upgrade_needs(upgrade, researcher)
+        }
+    }
+}
+
+fn upgrade_needs(upgrade: UpgradeId, researcher: Option<UnitTypeId>) -> Vec<Tech> {
+    let (previous, unlocked_by) = upgrade_requires(upgrade);
+    researcher
+        .into_iter()
+        .chain(unlocked_by)
+        .map(Tech::Structure)
+        .chain(previous.map(Tech::Upgrade))
+        .collect()
+}
+
+/// The tech an action needs directly
+#[must_use]
+pub fn action_requires(action: BuildOrderAction) -> Vec<Tech> {
+    match action {
+        BuildOrderAction::Train(unit, _) | BuildOrderAction::Produce(unit, _) => {
+            unit_requires(unit)
+                .iter()
+                .copied()
+                .map(Tech::Structure)
+                .collect()
+        }
+        BuildOrderAction::Construct(structure) => tech_requires(Tech::Structure(structure)),
+        BuildOrderAction::Research(upgrade, _, researcher) => {
+            upgrade_needs(upgrade, Some(researcher))
+        }
+        // the planner does the levels in order, the first one just needs somewhere to happen
+        BuildOrderAction::UpgradeTo(line, _) => researched_at(line.levels()[0])
+            .map(|(_, researcher)| Tech::Structure(researcher))
+            .into_iter()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Tech the action can get going without, but needs before it's finished,
+/// like the twilight council for level 2 weapons
+#[must_use]
+pub fn action_unlocks(action: BuildOrderAction) -> Vec<Tech> {
+    let BuildOrderAction::UpgradeTo(line, level) = action else {
+        return Vec::new();
+    };
+    let mut needs = Vec::new();
+    for upgrade in line.levels().into_iter().take(usize::from(level)).skip(1) {
+        if let Some(structure) = upgrade_requires(upgrade).1 {
+            if !needs.contains(&Tech::Structure(structure)) {
+                needs.push(Tech::Structure(structure));
+            }
+        }
+    }
+    needs
+}
+
+/// Everything the action needs up front that isn't in `have`, in an order that works:
+/// each one comes after whatever it needs itself
+#[must_use]
+pub fn missing_tech(action: BuildOrderAction, have: &HashSet<Tech>) -> Vec<Tech> {
+    missing_from(action_requires(action), have)
+}
+
+fn missing_from(needs: Vec<Tech>, have: &HashSet<Tech>) -> Vec<Tech> {
+    let mut missing = Vec::new();
+    for tech in needs {
+        add_missing(tech, have, &mut missing);
+    }
+    missing
+}
+
+fn add_missing(tech: Tech, have: &HashSet<Tech>, missing: &mut Vec<Tech>) {
+    if have.contains(&tech) || missing.contains(&tech) {
+        return;
+    }
+    for needed in tech_requires(tech) {
+        add_missing(needed, have, missing);
+    }
+    missing.push(tech);
+}
+
+/// A node that gets the tech, waiting on whatever that needs to be finished first.
+/// None for upgrades we don't know where to research.
+fn tech_component(tech: Tech, name: &str) -> Option<BuildComponent> {
+    use BuildCondition as C;
+    let (start, end, action) = match tech {
+        Tech::Structure(structure) => (
+            crate::structure_prerequisite(structure)
+                .map(C::StructureComplete)
+                .into_iter()
+                .collect(),
+            C::AtLeastCount(structure, 1),
+            BuildOrderAction::Construct(structure),
+        ),
+        Tech::Upgrade(upgrade) => {
+            let (ability, researcher) = researched_at(upgrade)?;
+            let previous = upgrade_requires(upgrade).0.map(C::TechComplete);
+            (
+                std::iter::once(C::StructureComplete(researcher))
+                    .chain(previous)
+                    .collect::<Vec<_>>(),
+                C::TechComplete(upgrade),
+                BuildOrderAction::Research(upgrade, ability, researcher),
+            )
+        }
+    };
+    Some(BuildComponent::new(
+        name,
+        ConditionGroup::new(&start, ConditionOperator::All),
+        ConditionGroup::new(&[end], ConditionOperator::All),
+        Some(action),
+        true,
+    ))
+}
+
+impl BuildOrderTree {
+    /// Adds `goal` under `parent`, along with whatever tech it needs that the build never gets.
+    /// Returns the goal's index.
+    pub fn add_goal(&mut self, goal: BuildComponent, parent: usize) -> Result<usize, TreeError> {
+        let index = self.add_node(goal, Some(parent))?;
+        self.require_tech(index)?;
+        Ok(index)
+    }
+
+    /// Goes through the build top down and gives every node the tech it needs.
+    /// Returns the nodes that were added.
+    pub fn resolve_prerequisites(&mut self) -> Result<Vec<usize>, TreeError> {
+        let mut added = Vec::new();
+        for index in self.breadth_first() {
+            added.extend(self.require_tech(index)?);
+        }
+        Ok(added)
+    }
+
+    /// Puts nodes above `node` for any tech its action needs that nothing in the build gets.
+    /// Tech it only needs later goes in a branch next to it instead, so it isn't held up.
+    /// Tech from another branch is left alone, it'll likely be there in time
+    /// and validation complains if the order is wrong.
+    pub fn require_tech(&mut self, node: usize) -> Result<Vec<usize>, TreeError> {
+        let component = self.get(node).ok_or(TreeError::NodeNotInTree)?;
+        let Some(action) = component.action() else {
+            return Ok(Vec::new());
+        };
+        let goal = component.name().to_string();
+        let name = |tech: Tech| format!("{tech} for {goal}");
+        let mut added = Vec::new();
+        for tech in missing_tech(action, &self.tech_in_build()) {
+            if let Some(prerequisite) = tech_component(tech, &name(tech)) {
+                added.push(self.insert_above(node, prerequisite)?);
+            }
+        }
+
+        let mut branch = self.parent(node);
+        for tech in missing_from(action_unlocks(action), &self.tech_in_build()) {
+            if let Some(unlock) = tech_component(tech, &name(tech)) {
+                let index = self.add_node(unlock, branch)?;
+                added.push(index);
+                branch = Some(index);
+            }
+        }
+        Ok(added)
+    }
+
+    /// Everything some node in the build constructs or researches, and the Nexus we start with
+    fn tech_in_build(&self) -> HashSet<Tech> {
+        let mut have = HashSet::from([Tech::Structure(UnitTypeId::Nexus)]);
+        for component in self.tree.values() {
+            match component.action() {
+                Some(BuildOrderAction::Construct(structure)) => {
+                    have.insert(Tech::Structure(structure));
+                }
+                Some(BuildOrderAction::Research(upgrade, _, _)) => {
+                    have.insert(Tech::Upgrade(upgrade));
+                }
+                Some(BuildOrderAction::UpgradeTo(line, level)) => {
+                    let levels = line.levels().into_iter().take(usize::from(level));
+                    have.extend(levels.map(Tech::Upgrade));
+                }
+                _ => (),
+            }
+        }
+        have
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_tree::TreePointer;
+
+    fn empty() -> ConditionGroup {
+        ConditionGroup::new(&[], ConditionOperator::All)
+    }
+
+    fn actions_down_to(tree: &BuildOrderTree, node: usize) -> Vec<Option<BuildOrderAction>> {
+        let mut chain = vec![tree.get(node).unwrap().action()];
+        let mut current = node;
+        while let Some(parent) = tree.parent(current) {
+            chain.push(tree.get(parent).unwrap().action());
+            current = parent;
+        }
+        chain.reverse();
+        chain
+    }
+
+    #[test]
+    fn charge_brings_its_tech_along() {
+        let mut tree = BuildOrderTree::default();
+        let charge = BuildComponent::new(
+            "charge",
+            empty(),
+            empty(),
+            Some(BuildOrderAction::Research(
+                UpgradeId::Charge,
+                AbilityId::ResearchCharge,
+                UnitTypeId::TwilightCouncil,
+            )),
+            true,
+        );
+        let index = tree.add_goal(charge, 0).unwrap();
+
+        assert_eq!(
+            actions_down_to(&tree, index),
+            vec![
+                None,
+                Some(BuildOrderAction::Construct(UnitTypeId::Gateway)),
+                Some(BuildOrderAction::Construct(UnitTypeId::CyberneticsCore)),
+                Some(BuildOrderAction::Construct(UnitTypeId::TwilightCouncil)),
+                Some(BuildOrderAction::Research(
+                    UpgradeId::Charge,
+                    AbilityId::ResearchCharge,
+                    UnitTypeId::TwilightCouncil,
+                )),
+            ]
+        );
+        assert_eq!(tree.validate(), vec![]);
+    }
+
+    #[test]
+    fn only_whats_missing_is_added() {
+        // the build gets a gateway and a robo, but forgot the core and the bay
+        let mut tree = TreePointer::new()
+            .root(
+                "gateway",
+                empty(),
+                empty(),
+                Some(BuildOrderAction::Construct(UnitTypeId::Gateway)),
+                true,
+            )
+            .and_then(|t| {
+                t.child(
+                    "robo",
+                    empty(),
+                    empty(),
+                    Some(BuildOrderAction::Construct(UnitTypeId::RoboticsFacility)),
+                    true,
+                )
+            })
+            .and_then(|t| {
+                t.child(
+                    "colossus",
+                    empty(),
+                    empty(),
+                    Some(BuildOrderAction::Train(
+                        UnitTypeId::Colossus,
+                        AbilityId::RoboticsFacilityTrainColossus,
+                    )),
+                    true,
+                )
+            })
+            .unwrap()
+            .tree;
+
+        let added = tree.resolve_prerequisites().unwrap();
+        let names: Vec<&str> = added.iter().map(|i| tree.get(*i).unwrap().name()).collect();
+        assert_eq!(
+            names,
+            vec!["CyberneticsCore for robo", "RoboticsBay for colossus"]
+        );
+        assert_eq!(tree.validate(), vec![]);
+        // everything is there now
+        assert!(tree.resolve_prerequisites().unwrap().is_empty());
+    }
+
+    #[test]
+    fn upgrade_levels_come_in_order() {
+        let have = HashSet::from([
+            Tech::Structure(UnitTypeId::Nexus),
+            Tech::Structure(UnitTypeId::Gateway),
+            Tech::Structure(UnitTypeId::CyberneticsCore),
+        ]);
+        assert_eq!(
+            missing_tech(
+                BuildOrderAction::Research(
+                    UpgradeId::ProtossGroundWeaponsLevel2,
+                    AbilityId::ForgeResearchProtossGroundWeaponsLevel2,
+                    UnitTypeId::Forge,
+                ),
+                &have
+            ),
+            vec![
+                Tech::Structure(UnitTypeId::Forge),
+                Tech::Structure(UnitTypeId::TwilightCouncil),
+                Tech::Upgrade(UpgradeId::ProtossGroundWeaponsLevel1),
+            ]
+        );
+    }
+
+    #[test]
+    fn checked_in_builds_need_nothing() {
+        for name in BuildOrderTree::available() {
+            let mut tree = BuildOrderTree::from_name(&name).unwrap();
+            assert_eq!(tree.resolve_prerequisites().unwrap(), vec![], "{name}");
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::{HashMap, HashSet};
+
+use rust_sc2::ids::{UnitTypeId, UpgradeId};
+
+use crate::{
+    build_orders::BuildOrderAction,
+    errors::BuildError,
+    protoss_bot::ReBiCycler,
+    tech_tree::{researched_at, upgrade_requires},
+};
+
+/// The leveled upgrades, each researched one level at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpgradeLine {
+    GroundWeapons,
+    GroundArmor,
+    Shields,
+    AirWeapons,
+    AirArmor,
+}
+
+impl UpgradeLine {
+    pub const ALL: [Self; 5] = [
+        Self::GroundWeapons,
+        Self::GroundArmor,
+        Self::Shields,
+        Self::AirWeapons,
+        Self::AirArmor,
+    ];
+
+    #[must_use]
+    pub const fn levels(self) -> [UpgradeId; 3] {
+        match self {
+            Self::GroundWeapons => [
+                UpgradeId::ProtossGroundWeaponsLevel1,
+                UpgradeId::ProtossGroundWeaponsLevel2,
+                UpgradeId::ProtossGroundWeaponsLevel3,
+            ],
+            Self::GroundArmor => [
+                UpgradeId::ProtossGroundArmorsLevel1,
+                UpgradeId::ProtossGroundArmorsLevel2,
+                UpgradeId::ProtossGroundArmorsLevel3,
+            ],
+            Self::Shields => [
+                UpgradeId::ProtossShieldsLevel1,
+                UpgradeId::ProtossShieldsLevel2,
+                UpgradeId::ProtossShieldsLevel3,
+            ],
+            Self::AirWeapons => [
+                UpgradeId::ProtossAirWeaponsLevel1,
+                UpgradeId::ProtossAirWeaponsLevel2,
+                UpgradeId::ProtossAirWeaponsLevel3,
+            ],
+            Self::AirArmor => [
+                UpgradeId::ProtossAirArmorsLevel1,
+                UpgradeId::ProtossAirArmorsLevel2,
+                UpgradeId::ProtossAirArmorsLevel3,
+            ],
+        }
+    }
+
+    /// levels finished so far
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn level(self, done: &HashSet<UpgradeId>) -> u8 {
+        self.levels()
+            .iter()
+            .take_while(|u| done.contains(u))
+            .count() as u8
+    }
+
+    /// The level to research next, if we're short of `target` and not already on it
+    pub fn next(self, target: u8, state: &UpgradeState) -> Option<UpgradeId> {
+        let levels = self.levels();
+        if levels.iter().any(|u| state.researching.contains(u)) {
+            return None;
+        }
+        let level = self.level(&state.done);
+        if level >= target.min(3) {
+            return None;
+        }
+        Some(levels[usize::from(level)])
+    }
+}
+
+/// What the planner needs to know about the game
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpgradeState {
+    pub done: HashSet<UpgradeId>,
+    pub researching: HashSet<UpgradeId>,
+    /// finished structures, for the twilight council and fleet beacon
+    pub ready: HashSet<UnitTypeId>,
+    /// how many of each researcher are free to start something
+    pub idle: HashMap<UnitTypeId, usize>,
+}
+
+/// Picks research to start now so each line heads for its target level.
+/// Lines furthest behind go first, so two forges end up doing weapons and armor side by side,
+/// ties go in the order the targets are given.
+pub fn plan_upgrades(targets: &[(UpgradeLine, u8)], state: &UpgradeState) -> Vec<BuildOrderAction> {
+    let mut candidates: Vec<(u8, usize, UpgradeId)> = targets
+        .iter()
+        .enumerate()
+        .filter_map(|(order, (line, target))| {
+            let upgrade = line.next(*target, state)?;
+            let unlocked = upgrade_requires(upgrade)
+                .1
+                .is_none_or(|needs| state.ready.contains(&needs));
+            unlocked.then(|| (line.level(&state.done), order, upgrade))
+        })
+        .collect();
+    candidates.sort_by_key(|(level, order, _)| (*level, *order));
+    candidates.dedup_by_key(|(_, _, upgrade)| *upgrade);
+
+    let mut idle = state.idle.clone();
+    candidates
+        .into_iter()
+        .filter_map(|(_, _, upgrade)| {
+            let (ability, researcher) = researched_at(upgrade)?;
+            let free = idle.get_mut(&researcher).filter(|n| **n > 0)?;
+            *free -= 1;
+            Some(BuildOrderAction::Research(upgrade, ability, researcher))
+        })
+        .collect()
+}
+
+/// the targets among a step's funded actions
+pub fn upgrade_targets(actions: &[BuildOrderAction]) -> Vec<(UpgradeLine, u8)> {
+    actions
+        .iter()
+        .filter_map(|action| match action {
+            BuildOrderAction::UpgradeTo(line, level) => Some((*line, *level)),
+            _ => None,
+        })
+        .collect()
+}
+
+impl ReBiCycler {
+    /// Starts whatever research the targets call for, each on its own idle researcher
+    pub fn run_upgrades(&mut self, targets: &[(UpgradeLine, u8)]) {
+        let mut used = HashSet::new();
+        for action in plan_upgrades(targets, &self.upgrade_state()) {
+            let BuildOrderAction::Research(upgrade, ability, researcher) = action else {
+                continue;
+            };
+            let result = self
+                .units
+                .my
+                .structures
+                .of_type(researcher)
+                .ready()
+                .idle()
+                .iter()
+                .find(|u| u.has_ability(ability) && !used.contains(&u.tag()))
+                .map(|unit| {
+                    used.insert(unit.tag());
+                    unit.use_ability(ability, false);
+                })
+                .ok_or(BuildError::NoTrainer);
+            if let Err(e) = result {
+                self.log_error(format!("Couldn't start {upgrade:?}: {e:?}"));
+            }
+        }
+    }
+
+    pub fn upgrade_state(&self) -> UpgradeState {
+        let structures = &self.units.my.structures;
+        let mut state = UpgradeState::default();
+        for upgrade in UpgradeLine::ALL.iter().flat_map(|line| line.levels()) {
+            if self.has_upgrade(upgrade) {
+                state.done.insert(upgrade);
+            } else if self.is_ordered_upgrade(upgrade) {
+                state.researching.insert(upgrade);
+            }
+        }
+        for structure in structures.ready().iter() {
+            state.ready.insert(structure.type_id());
+            if structure.is_idle() {
+                *state.idle.entry(structure.type_id()).or_default() += 1;
+            }
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_sc2::ids::AbilityId;
+
+    use super::*;
+
+    fn state(ready: &[UnitTypeId], idle: &[(UnitTypeId, usize)]) -> UpgradeState {
+        UpgradeState {
+            ready: ready.iter().copied().collect(),
+            idle: idle.iter().copied().collect(),
+            ..UpgradeState::default()
+        }
+    }
+
+    fn upgrades(actions: &[BuildOrderAction]) -> Vec<UpgradeId> {
+        actions
+            .iter()
+            .filter_map(|a| match a {
+                BuildOrderAction::Research(upgrade, _, _) => Some(*upgrade),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn forges_share_the_lines() {
+        let targets = [
+            (UpgradeLine::GroundWeapons, 3),
+            (UpgradeLine::GroundArmor, 3),
+            (UpgradeLine::Shields, 1),
+        ];
+        let mut game = state(&[UnitTypeId::Forge], &[(UnitTypeId::Forge, 2)]);
+        assert_eq!(
+            plan_upgrades(&targets, &game),
+            vec![
+                BuildOrderAction::Research(
+                    UpgradeId::ProtossGroundWeaponsLevel1,
+                    AbilityId::ForgeResearchProtossGroundWeaponsLevel1,
+                    UnitTypeId::Forge,
+                ),
+                BuildOrderAction::Research(
+                    UpgradeId::ProtossGroundArmorsLevel1,
+                    AbilityId::ForgeResearchProtossGroundArmorLevel1,
+                    UnitTypeId::Forge,
+                ),
+            ]
+        );
+
+        // weapons 1 is done and armor is still going: shields is further behind than weapons 2
+        game.done.insert(UpgradeId::ProtossGroundWeaponsLevel1);
+        game.researching
+            .insert(UpgradeId::ProtossGroundArmorsLevel1);
+        game.idle.insert(UnitTypeId::Forge, 1);
+        game.ready.insert(UnitTypeId::TwilightCouncil);
+        assert_eq!(
+            upgrades(&plan_upgrades(&targets, &game)),
+            vec![UpgradeId::ProtossShieldsLevel1]
+        );
+    }
+
+    #[test]
+    fn level_two_waits_for_the_twilight_council() {
+        let targets = [(UpgradeLine::GroundWeapons, 2)];
+        let mut game = state(&[UnitTypeId::Forge], &[(UnitTypeId::Forge, 1)]);
+        game.done.insert(UpgradeId::ProtossGroundWeaponsLevel1);
+        assert!(plan_upgrades(&targets, &game).is_empty());
+
+        game.ready.insert(UnitTypeId::TwilightCouncil);
+        assert_eq!(
+            upgrades(&plan_upgrades(&targets, &game)),
+            vec![UpgradeId::ProtossGroundWeaponsLevel2]
+        );
+
+        // and stops at the target
+        game.done.insert(UpgradeId::ProtossGroundWeaponsLevel2);
+        assert!(plan_upgrades(&targets, &game).is_empty());
+    }
+
+    #[test]
+    fn air_upgrades_go_to_the_core() {
+        let targets = [(UpgradeLine::AirWeapons, 3), (UpgradeLine::AirArmor, 1)];
+        let mut game = state(
+            &[UnitTypeId::CyberneticsCore],
+            &[(UnitTypeId::CyberneticsCore, 1), (UnitTypeId::Forge, 1)],
+        );
+        assert_eq!(
+            upgrades(&plan_upgrades(&targets, &game)),
+            vec![UpgradeId::ProtossAirWeaponsLevel1]
+        );
+
+        game.done.insert(UpgradeId::ProtossAirWeaponsLevel1);
+        game.done.insert(UpgradeId::ProtossAirArmorsLevel1);
+        assert!(plan_upgrades(&targets, &game).is_empty());
+        game.ready.insert(UnitTypeId::FleetBeacon);
+        assert_eq!(
+            upgrades(&plan_upgrades(&targets, &game)),
+            vec![UpgradeId::ProtossAirWeaponsLevel2]
+        );
+    }
+}
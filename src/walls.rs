@@ -0,0 +1,528 @@
+use std::collections::HashSet;
+
+use rust_sc2::prelude::*;
+
+use crate::{
+    map_analysis::{Choke, Ramp},
+    placement_grid::{PlacementGrid, Tile},
+    protoss_bot::ReBiCycler,
+    PYLON_POWER_RADIUS,
+};
+
+/// how far behind the gap we keep clear, so whatever comes through has somewhere to go
+const GAP_DEPTH: usize = 2;
+/// how far from the wall we look for its pylon
+const PYLON_SEARCH_RADIUS: f32 = 8.0;
+/// how many placements we'll try before deciding the line can't be walled
+const SEARCH_BUDGET: usize = 5000;
+
+/// What's left open in the wall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WallGap {
+    /// nothing gets through
+    Closed,
+    /// one tile, for a zealot on hold position
+    Zealot,
+    /// two tiles with a shield battery site in them
+    ShieldBattery,
+}
+
+impl WallGap {
+    /// Zerglings need holding at the door and Protoss get a battery site in theirs.
+    /// Nobody else gets a gap planned, the path check still won't let the last piece shut us in.
+    pub const fn against(race: Race) -> Self {
+        match race {
+            Race::Zerg => Self::Zealot,
+            Race::Protoss => Self::ShieldBattery,
+            _ => Self::Closed,
+        }
+    }
+
+    const fn tiles(self) -> usize {
+        match self {
+            Self::Closed => 0,
+            Self::Zealot => 1,
+            Self::ShieldBattery => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WallError {
+    /// no way to fit buildings over the whole line
+    NoFit,
+    /// nowhere behind the wall for a pylon that reaches all of it
+    NoPylonSpot,
+}
+
+/// One building of a wall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WallPiece {
+    pub structure: UnitTypeId,
+    /// the bottom left tile of its footprint
+    pub corner: Tile,
+    pub tiles: usize,
+}
+
+impl WallPiece {
+    const fn new(structure: UnitTypeId, corner: Tile, tiles: usize) -> Self {
+        Self {
+            structure,
+            corner,
+            tiles,
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn center(&self) -> Point2 {
+        let half = self.tiles as f32 / 2.0;
+        Point2::new(self.corner.0 as f32 + half, self.corner.1 as f32 + half)
+    }
+
+    fn footprint(&self) -> impl Iterator<Item = Tile> {
+        PlacementGrid::footprint(self.corner, self.tiles)
+    }
+}
+
+/// A line of tiles to close off, and which way is our side of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct WallPlan {
+    line: Vec<Tile>,
+    /// a unit step from the outside in
+    inward: (f32, f32),
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn tile_center((x, y): Tile) -> Point2 {
+    Point2::new(x as f32 + 0.5, y as f32 + 0.5)
+}
+
+fn direction(from: Point2, to: Point2) -> (f32, f32) {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length = dx.hypot(dy).max(f32::EPSILON);
+    (dx / length, dy / length)
+}
+
+/// the tiles on a straight line between two, ends included
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn tiles_between(a: Tile, b: Tile) -> Vec<Tile> {
+    let (from, to) = (tile_center(a), tile_center(b));
+    let steps = from.distance(to).ceil().max(1.0) as usize;
+    let mut tiles: Vec<Tile> = (0..=steps)
+        .filter_map(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let t = i as f32 / steps as f32;
+            PlacementGrid::tile_at(from + (to - from) * t)
+        })
+        .collect();
+    tiles.dedup();
+    tiles
+}
+
+impl WallPlan {
+    /// across the top of the ramp, on the high ground
+    pub fn main_ramp(ramp: &Ramp) -> Self {
+        Self::new(ramp.upper.clone(), direction(ramp.bottom, ramp.top))
+    }
+
+    /// across the choke into the natural, from one side to the other
+    pub fn natural(choke: &Choke, natural: Point2) -> Self {
+        let ends = (
+            PlacementGrid::tile_at(choke.ends.0),
+            PlacementGrid::tile_at(choke.ends.1),
+        );
+        let line = match ends {
+            (Some(a), Some(b)) => tiles_between(a, b),
+            _ => Vec::new(),
+        };
+        Self::new(line, direction(choke.center, natural))
+    }
+
+    /// The line gets sorted along the wall, so the gap can go in the middle
+    pub fn new(mut line: Vec<Tile>, inward: (f32, f32)) -> Self {
+        let along = |t: &Tile| {
+            let p = tile_center(*t);
+            p.y.mul_add(inward.0, -p.x * inward.1)
+        };
+        line.sort_by(|a, b| along(a).total_cmp(&along(b)));
+        line.dedup();
+        Self { line, inward }
+    }
+
+    fn depth(&self, point: Point2) -> f32 {
+        point.x.mul_add(self.inward.0, point.y * self.inward.1)
+    }
+
+    /// the tile `steps` further in from this one
+    #[allow(clippy::cast_precision_loss)]
+    fn behind(&self, tile: Tile, steps: usize) -> Option<Tile> {
+        let distance = steps as f32;
+        PlacementGrid::tile_at(
+            tile_center(tile).offset(self.inward.0 * distance, self.inward.1 * distance),
+        )
+    }
+
+    /// Lays out gateways and a core over the line with the gap in the middle,
+    /// and a pylon behind them that powers the lot. Pylons fill in where a gateway won't fit.
+    /// `can_use` says whether a square footprint could go at a corner.
+    /// # Errors
+    /// - `WallError::NoFit` if there's no way to cover the line
+    /// - `WallError::NoPylonSpot` if nothing behind the wall can power all of it
+    pub fn layout<F>(&self, gap: WallGap, can_use: F) -> Result<Vec<WallPiece>, WallError>
+    where
+        F: Fn(Tile, usize) -> bool,
+    {
+        let start = self.line.len().saturating_sub(gap.tiles()) / 2;
+        let gap_tiles: Vec<Tile> = self
+            .line
+            .iter()
+            .skip(start)
+            .take(gap.tiles())
+            .copied()
+            .collect();
+        let mut search = Search {
+            plan: self,
+            can_use,
+            keep_clear: gap_tiles.iter().copied().collect(),
+            taken: HashSet::new(),
+            pieces: Vec::new(),
+            budget: SEARCH_BUDGET,
+        };
+        for tile in &gap_tiles {
+            search
+                .keep_clear
+                .extend((1..=GAP_DEPTH).filter_map(|steps| self.behind(*tile, steps)));
+        }
+
+        if gap == WallGap::ShieldBattery {
+            let battery = search.battery_corner(&gap_tiles).ok_or(WallError::NoFit)?;
+            search.place(WallPiece::new(UnitTypeId::ShieldBattery, battery, 2));
+        }
+
+        if !search.cover() {
+            return Err(WallError::NoFit);
+        }
+        let mut pieces = search.pieces;
+        // the core goes second, so the first gateway can start it
+        if let Some(core) = pieces
+            .iter_mut()
+            .filter(|p| p.structure == UnitTypeId::Gateway)
+            .nth(1)
+        {
+            core.structure = UnitTypeId::CyberneticsCore;
+        }
+
+        let taken: HashSet<Tile> = pieces.iter().flat_map(WallPiece::footprint).collect();
+        let keep_clear = search.keep_clear;
+        let usable = |corner: Tile| {
+            (search.can_use)(corner, 2)
+                && PlacementGrid::footprint(corner, 2)
+                    .all(|t| !taken.contains(&t) && !keep_clear.contains(&t))
+        };
+        let pylon = self
+            .pylon_corner(&pieces, usable)
+            .ok_or(WallError::NoPylonSpot)?;
+        pieces.push(WallPiece::new(UnitTypeId::Pylon, pylon, 2));
+        Ok(pieces)
+    }
+
+    /// a pylon behind the wall, reaching every piece that needs it, as close in as it'll go
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn pylon_corner<F>(&self, pieces: &[WallPiece], usable: F) -> Option<Tile>
+    where
+        F: Fn(Tile) -> bool,
+    {
+        let middle = tile_center(*self.line.get(self.line.len() / 2)?);
+        let wall_depth = pieces
+            .iter()
+            .map(|p| self.depth(p.center()))
+            .fold(f32::MIN, f32::max);
+        let low = PlacementGrid::tile_at(middle.offset(-PYLON_SEARCH_RADIUS, -PYLON_SEARCH_RADIUS))
+            .unwrap_or((0, 0));
+        let span = (PYLON_SEARCH_RADIUS * 2.0) as usize;
+        PlacementGrid::footprint(low, span)
+            .map(|corner| WallPiece::new(UnitTypeId::Pylon, corner, 2))
+            .filter(|pylon| self.depth(pylon.center()) >= wall_depth)
+            .filter(|pylon| {
+                pieces
+                    .iter()
+                    .filter(|p| crate::structure_needs_power(&p.structure))
+                    .all(|p| p.center().distance(pylon.center()) <= PYLON_POWER_RADIUS)
+            })
+            .filter(|pylon| usable(pylon.corner))
+            .min_by(|a, b| {
+                a.center()
+                    .distance(middle)
+                    .total_cmp(&b.center().distance(middle))
+            })
+            .map(|pylon| pylon.corner)
+    }
+}
+
+/// Works along the line putting a building over each tile nothing covers yet,
+/// backing up when it paints itself into a corner
+struct Search<'a, F> {
+    plan: &'a WallPlan,
+    can_use: F,
+    /// the gap and the way in behind it
+    keep_clear: HashSet<Tile>,
+    taken: HashSet<Tile>,
+    pieces: Vec<WallPiece>,
+    /// how many more tries before we give up on the line
+    budget: usize,
+}
+
+impl<F: Fn(Tile, usize) -> bool> Search<'_, F> {
+    fn place(&mut self, piece: WallPiece) {
+        self.taken.extend(piece.footprint());
+        self.pieces.push(piece);
+    }
+
+    fn unplace(&mut self) {
+        if let Some(piece) = self.pieces.pop() {
+            for tile in piece.footprint() {
+                self.taken.remove(&tile);
+            }
+        }
+    }
+
+    /// Corners that put a free footprint over `tile`,
+    /// the ones covering most of the line first, then the ones furthest in
+    fn corners(&self, (x, y): Tile, size: usize) -> Vec<Tile> {
+        let mut corners: Vec<(Tile, usize, f32)> = (0..size)
+            .flat_map(|dx| {
+                (0..size).filter_map(move |dy| Some((x.checked_sub(dx)?, y.checked_sub(dy)?)))
+            })
+            .filter(|corner| {
+                (self.can_use)(*corner, size)
+                    && PlacementGrid::footprint(*corner, size)
+                        .all(|t| !self.taken.contains(&t) && !self.keep_clear.contains(&t))
+            })
+            .map(|corner| {
+                let piece = WallPiece::new(UnitTypeId::Pylon, corner, size);
+                let covers = piece
+                    .footprint()
+                    .filter(|t| self.plan.line.contains(t))
+                    .count();
+                (corner, covers, self.plan.depth(piece.center()))
+            })
+            .collect();
+        corners.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.total_cmp(&a.2)));
+        corners.into_iter().map(|(corner, _, _)| corner).collect()
+    }
+
+    /// a battery footprint sitting right over the gap, as far in as it'll go
+    fn battery_corner(&self, gap: &[Tile]) -> Option<Tile> {
+        let (x, y) = *gap.first()?;
+        (0..2)
+            .flat_map(|dx| {
+                (0..2).filter_map(move |dy| Some((x.checked_sub(dx)?, y.checked_sub(dy)?)))
+            })
+            .filter(|corner| {
+                let mut footprint = PlacementGrid::footprint(*corner, 2);
+                (self.can_use)(*corner, 2)
+                    && gap
+                        .iter()
+                        .all(|t| PlacementGrid::footprint(*corner, 2).any(|f| f == *t))
+                    && footprint.all(|t| !self.taken.contains(&t))
+            })
+            .max_by(|a, b| {
+                let depth = |corner: &Tile| {
+                    self.plan
+                        .depth(WallPiece::new(UnitTypeId::ShieldBattery, *corner, 2).center())
+                };
+                depth(a).total_cmp(&depth(b))
+            })
+    }
+
+    /// whether everything on the line that isn't the gap got covered
+    fn cover(&mut self) -> bool {
+        let next = self
+            .plan
+            .line
+            .iter()
+            .find(|t| !self.taken.contains(t) && !self.keep_clear.contains(t));
+        let Some(&tile) = next else {
+            return true;
+        };
+        if self.budget == 0 {
+            return false;
+        }
+        self.budget -= 1;
+
+        let gateways = self.corners(tile, 3).into_iter().map(|c| (c, 3));
+        let pylons = self.corners(tile, 2).into_iter().map(|c| (c, 2));
+        let options: Vec<(Tile, usize)> = gateways.chain(pylons).collect();
+        for (corner, size) in options {
+            let structure = if size == 3 {
+                UnitTypeId::Gateway
+            } else {
+                UnitTypeId::Pylon
+            };
+            self.place(WallPiece::new(structure, corner, size));
+            if self.cover() {
+                return true;
+            }
+            self.unplace();
+        }
+        false
+    }
+}
+
+impl ReBiCycler {
+    /// Zerg get walled at the natural, everyone else at the top of the main ramp,
+    /// leaving `gap` open either way
+    pub fn plan_walls(&mut self, gap: WallGap) {
+        let natural = self.map_target(crate::build_orders::MapTarget::OurNatural);
+        let plan = if self.enemy_race == Race::Zerg {
+            self.map_analysis
+                .natural_choke()
+                .map(|choke| WallPlan::natural(choke, natural))
+        } else {
+            self.map_analysis.main_ramp().map(WallPlan::main_ramp)
+        };
+        let Some(plan) = plan else {
+            self.log_error("Nowhere to wall off".to_string());
+            return;
+        };
+        let director = &self.siting_director;
+        match plan.layout(gap, |corner, tiles| director.could_wall(corner, tiles)) {
+            Ok(pieces) => {
+                let added = self.siting_director.add_wall(&pieces);
+                if added < pieces.len() {
+                    self.log_error(format!("Only {added} of {} wall pieces fit", pieces.len()));
+                }
+            }
+            Err(e) => self.log_error(format!("Couldn't plan a wall: {e:?}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a five wide ramp coming up from the left onto high ground at x = 12 and over
+    fn ramp() -> Ramp {
+        Ramp {
+            tiles: Vec::new(),
+            upper: (4..9).map(|y| (12, y)).collect(),
+            lower: (4..9).map(|y| (7, y)).collect(),
+            top: Point2::new(12.5, 6.5),
+            bottom: Point2::new(7.5, 6.5),
+            width: 5.0,
+        }
+    }
+
+    /// everything from x = 12 up, with the cliff edge running past the ramp on both sides
+    fn high_ground(corner: Tile, tiles: usize) -> bool {
+        PlacementGrid::footprint(corner, tiles).all(|(x, y)| x >= 12 && y <= 14)
+    }
+
+    fn covered(pieces: &[WallPiece]) -> HashSet<Tile> {
+        pieces.iter().flat_map(WallPiece::footprint).collect()
+    }
+
+    /// the pylon that powers the wall comes last, filler pylons don't need power
+    fn check_power(pieces: &[WallPiece]) {
+        let pylon = pieces.last().unwrap();
+        assert_eq!(pylon.structure, UnitTypeId::Pylon);
+        for piece in pieces.iter().filter(|p| p.structure != UnitTypeId::Pylon) {
+            assert!(
+                piece.center().distance(pylon.center()) <= PYLON_POWER_RADIUS,
+                "{pieces:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn zealot_gap_leaves_one_tile() {
+        let ramp = ramp();
+        let pieces = WallPlan::main_ramp(&ramp)
+            .layout(WallGap::Zealot, high_ground)
+            .unwrap();
+        let covered = covered(&pieces);
+        let open: Vec<&Tile> = ramp.upper.iter().filter(|t| !covered.contains(t)).collect();
+        assert_eq!(open, vec![&(12, 6)], "{pieces:?}");
+        // and the way in behind it is clear
+        assert!(!covered.contains(&(13, 6)) && !covered.contains(&(14, 6)));
+
+        let structures: Vec<UnitTypeId> = pieces.iter().map(|p| p.structure).collect();
+        assert!(
+            structures.contains(&UnitTypeId::CyberneticsCore),
+            "{pieces:?}"
+        );
+        assert!(structures.contains(&UnitTypeId::Gateway), "{pieces:?}");
+        // nothing on top of anything else
+        let tiles: usize = pieces.iter().map(|p| p.tiles * p.tiles).sum();
+        assert_eq!(tiles, covered.len());
+        check_power(&pieces);
+    }
+
+    #[test]
+    fn closed_wall_covers_everything() {
+        let ramp = ramp();
+        let pieces = WallPlan::main_ramp(&ramp)
+            .layout(WallGap::Closed, high_ground)
+            .unwrap();
+        let covered = covered(&pieces);
+        assert!(ramp.upper.iter().all(|t| covered.contains(t)), "{pieces:?}");
+        check_power(&pieces);
+    }
+
+    #[test]
+    fn battery_goes_in_the_gap() {
+        let ramp = ramp();
+        let pieces = WallPlan::main_ramp(&ramp)
+            .layout(WallGap::ShieldBattery, high_ground)
+            .unwrap();
+        let battery = pieces
+            .iter()
+            .find(|p| p.structure == UnitTypeId::ShieldBattery)
+            .unwrap();
+        let under: Vec<Tile> = battery
+            .footprint()
+            .filter(|t| ramp.upper.contains(t))
+            .collect();
+        assert_eq!(under.len(), 2, "{pieces:?}");
+        let covered = covered(&pieces);
+        assert!(ramp.upper.iter().all(|t| covered.contains(t)), "{pieces:?}");
+        check_power(&pieces);
+    }
+
+    #[test]
+    fn no_room_no_wall() {
+        // a cliff right behind the top of the ramp
+        let cramped = |corner: Tile, tiles: usize| {
+            PlacementGrid::footprint(corner, tiles).all(|(x, _)| x == 12)
+        };
+        assert_eq!(
+            WallPlan::main_ramp(&ramp()).layout(WallGap::Zealot, cramped),
+            Err(WallError::NoFit)
+        );
+    }
+
+    #[test]
+    fn natural_wall_runs_between_the_ends() {
+        let choke = Choke {
+            center: Point2::new(20.5, 10.5),
+            ends: (Point2::new(16.5, 10.5), Point2::new(24.5, 10.5)),
+            width: 9.0,
+        };
+        let plan = WallPlan::natural(&choke, Point2::new(20.5, 25.5));
+        assert_eq!(plan.line.len(), 9);
+        let pieces = plan
+            .layout(WallGap::Zealot, |corner, tiles| {
+                PlacementGrid::footprint(corner, tiles)
+                    .all(|(x, y)| (16..=24).contains(&x) && y >= 10)
+            })
+            .unwrap();
+        let covered = covered(&pieces);
+        assert_eq!(
+            plan.line.iter().filter(|t| !covered.contains(t)).count(),
+            1,
+            "{pieces:?}"
+        );
+        check_power(&pieces);
+    }
+}
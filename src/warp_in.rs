@@ -0,0 +1,556 @@
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::TAU;
+
+use rust_sc2::prelude::*;
+
+use crate::{
+    budget::Budget, build_orders::MapTarget, errors::BuildError, nexus_energy::THREAT_RADIUS,
+    protoss_bot::ReBiCycler,
+};
+
+/// room each warping unit takes up
+const WARP_SPACING: f32 = 2.0;
+/// gates this close to coming off cooldown get waited for, so the whole round lands together
+const BATCH_WINDOW: f32 = 3.0;
+/// how far outside an enemy's range still counts as under fire
+const FIRE_MARGIN: f32 = 1.5;
+/// a spot is on the wrong side of a cliff when walking to the target
+/// takes this much longer than the straight line
+const DETOUR_FACTOR: f32 = 1.3;
+const DETOUR_SLACK: f32 = 3.0;
+/// how far from the target to look for somewhere walkable to measure from
+const TARGET_SEARCH_RADIUS: i32 = 6;
+/// a warp-in that hasn't started this long after we asked for it isn't going to
+const ORDER_TIMEOUT: f32 = 3.0;
+
+impl ReBiCycler {
+    /// Warps in from every warpgate that's off cooldown, as many as `spare` pays for past the first.
+    pub fn warp_in(
+        &mut self,
+        unit_type: UnitTypeId,
+        ability: AbilityId,
+        spare: &mut Budget,
+    ) -> Result<(), BuildError> {
+        let now = self.time;
+        let gates: Vec<u64> = self
+            .units
+            .my
+            .structures
+            .of_type(UnitTypeId::WarpGate)
+            .ready()
+            .iter()
+            .map(Unit::tag)
+            .collect();
+        let batch: Vec<u64> = self
+            .warp_gates
+            .batch(&gates, now)
+            .into_iter()
+            .filter(|tag| {
+                self.units
+                    .my
+                    .structures
+                    .get(*tag)
+                    .is_some_and(|g| g.has_ability(ability))
+            })
+            .collect();
+        if batch.is_empty() {
+            return Err(BuildError::AllBusy(ability));
+        }
+
+        let cost = self.get_unit_cost(unit_type);
+        let cost = Budget::new(cost.minerals, cost.vespene);
+        let mut count = 1;
+        while count < batch.len() && spare.covers(cost) {
+            spare.spend(cost);
+            count += 1;
+        }
+
+        let spots = plan_warp_ins(
+            &self.fast_matrices(),
+            self.warp_target(),
+            count,
+            &self.enemy_fire(),
+            &self.warp_terrain(),
+        );
+        if spots.is_empty() {
+            for _ in 1..count {
+                spare.add(cost);
+            }
+            return Err(BuildError::NoPlacementLocations);
+        }
+        // whatever we couldn't find room for goes back in the pot
+        for _ in spots.len()..count {
+            spare.add(cost);
+        }
+        for (tag, spot) in batch.into_iter().zip(spots) {
+            if let Some(gate) = self.units.my.structures.get(tag) {
+                gate.warp_in(unit_type, spot);
+                self.warp_gates.ordered(tag, unit_type, ability, now);
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts the cooldown on gates whose warp-in went through.
+    /// A gate that's warping in loses its warp abilities until it's ready again.
+    pub fn track_warp_ins(&mut self) {
+        let mut gates = std::mem::take(&mut self.warp_gates);
+        gates.confirm(self.time, |gate, ability| {
+            self.units
+                .my
+                .structures
+                .get(gate)
+                .is_none_or(|gate| gate.has_ability(ability))
+        });
+        self.warp_gates = gates;
+    }
+
+    /// matrices with a pylon, nexus or phasing prism in them warp in quickly
+    fn fast_matrices(&self) -> Vec<Matrix> {
+        let boosters = self.units.my.all.of_types(&vec![
+            UnitTypeId::Pylon,
+            UnitTypeId::Nexus,
+            UnitTypeId::WarpPrismPhasing,
+        ]);
+        self.state
+            .observation
+            .raw
+            .psionic_matrix
+            .iter()
+            .filter(|m| !boosters.closer(m.radius, m.pos).is_empty())
+            .map(|m| Matrix {
+                pos: m.pos,
+                radius: m.radius,
+            })
+            .collect()
+    }
+
+    /// a base with enemies at it, otherwise wherever the army is rallying
+    fn warp_target(&self) -> Point2 {
+        let enemies = self.units.enemy.units.filter(|u| !u.is_worker());
+        self.units
+            .my
+            .townhalls
+            .iter()
+            .find(|base| !enemies.closer(THREAT_RADIUS, base.position()).is_empty())
+            .map(Unit::position)
+            .or_else(|| self.army_manager.rally_point())
+            .unwrap_or_else(|| self.map_target(MapTarget::OurNatural))
+    }
+
+    /// the pathing and placement grids, copied out for the planner
+    fn warp_terrain(&self) -> WarpTerrain {
+        let (width, height) = (self.game_info.map_size.x, self.game_info.map_size.y);
+        let cells = || (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)));
+        WarpTerrain::new(
+            width,
+            height,
+            cells().map(|cell| self.is_pathable(cell)).collect(),
+            cells().map(|cell| self.is_placeable(cell)).collect(),
+        )
+    }
+
+    fn enemy_fire(&self) -> Vec<Threat> {
+        self.units
+            .enemy
+            .all
+            .iter()
+            .filter(|u| u.can_attack_ground())
+            .map(|u| Threat {
+                pos: u.position(),
+                range: u.ground_range() + u.radius(),
+            })
+            .collect()
+    }
+}
+
+/// A psionic matrix we can warp into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix {
+    pub pos: Point2,
+    pub radius: f32,
+}
+
+/// Something that can shoot at units on the ground
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Threat {
+    pub pos: Point2,
+    pub range: f32,
+}
+
+/// The pathing and placement grids, copied out once for a round of warp-ins
+#[derive(Debug, Clone)]
+pub struct WarpTerrain {
+    width: usize,
+    height: usize,
+    pathable: Vec<bool>,
+    placeable: Vec<bool>,
+}
+
+impl WarpTerrain {
+    pub fn new(width: usize, height: usize, pathable: Vec<bool>, placeable: Vec<bool>) -> Self {
+        Self {
+            width,
+            height,
+            pathable,
+            placeable,
+        }
+    }
+
+    /// walkable and placeable everywhere, for tests that don't care about terrain
+    #[cfg(test)]
+    pub fn open(width: usize, height: usize) -> Self {
+        let everywhere = vec![true; width * height];
+        Self::new(width, height, everywhere.clone(), everywhere)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn cell(&self, point: Point2) -> Option<usize> {
+        if point.x < 0.0 || point.y < 0.0 {
+            return None;
+        }
+        let (x, y) = (point.x as usize, point.y as usize);
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+
+    fn can_warp(&self, point: Point2) -> bool {
+        self.cell(point)
+            .is_some_and(|c| self.pathable[c] && self.placeable[c])
+    }
+
+    /// Steps it takes to walk from the walkable cell nearest `from` to every other cell.
+    /// Cells we can't get to are `None`.
+    #[allow(clippy::cast_possible_wrap)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    fn walking_steps(&self, from: Point2) -> Vec<Option<u32>> {
+        let mut steps = vec![None; self.pathable.len()];
+        let start = (0..=TARGET_SEARCH_RADIUS)
+            .flat_map(|r| (-r..=r).flat_map(move |dx| (-r..=r).map(move |dy| (dx, dy))))
+            .map(|(dx, dy)| from + Point2::new(dx as f32, dy as f32))
+            .filter_map(|p| self.cell(p))
+            .find(|c| self.pathable[*c]);
+        let Some(start) = start else {
+            return steps;
+        };
+
+        steps[start] = Some(0);
+        let mut queue = VecDeque::from([start]);
+        while let Some(current) = queue.pop_front() {
+            let (x, y) = ((current % self.width) as i64, (current / self.width) as i64);
+            let next_step = steps[current].map(|s| s + 1);
+            for (dx, dy) in [
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ] {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= self.width as i64 || ny >= self.height as i64 {
+                    continue;
+                }
+                let next = ny as usize * self.width + nx as usize;
+                if self.pathable[next] && steps[next].is_none() {
+                    steps[next] = next_step;
+                    queue.push_back(next);
+                }
+            }
+        }
+        steps
+    }
+}
+
+/// Up to `count` spots to warp into, closest to `target` first.
+/// Matrices nearer the target are used up before further ones. Spots in range of
+/// `threats`, or that would have to walk the long way round to reach the target, are skipped.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+pub fn plan_warp_ins(
+    matrices: &[Matrix],
+    target: Point2,
+    count: usize,
+    threats: &[Threat],
+    terrain: &WarpTerrain,
+) -> Vec<Point2> {
+    let steps = terrain.walking_steps(target);
+    let close_enough = |spot: Point2| {
+        terrain
+            .cell(spot)
+            .and_then(|c| steps[c])
+            .is_some_and(|walk| {
+                walk as f32 <= spot.distance(target).mul_add(DETOUR_FACTOR, DETOUR_SLACK)
+            })
+    };
+    let safe = |spot: Point2| {
+        threats
+            .iter()
+            .all(|t| t.pos.distance(spot) > t.range + FIRE_MARGIN)
+    };
+
+    let mut by_distance = matrices.to_vec();
+    by_distance.sort_by(|a, b| a.pos.distance(target).total_cmp(&b.pos.distance(target)));
+
+    let mut spots: Vec<Point2> = Vec::new();
+    for matrix in by_distance {
+        let rings = (matrix.radius / WARP_SPACING).floor();
+        let mut candidates: Vec<Point2> = (1..rings as u32)
+            .flat_map(|ring| {
+                let radius = ring as f32 * WARP_SPACING;
+                let angles = ((TAU * radius) / WARP_SPACING).floor().max(1.0);
+                (0..angles as u32).map(move |step| {
+                    matrix.pos + Point2::new(radius, 0.0).rotate(TAU * step as f32 / angles)
+                })
+            })
+            .filter(|spot| terrain.can_warp(*spot) && safe(*spot) && close_enough(*spot))
+            .collect();
+        candidates.sort_by(|a, b| a.distance(target).total_cmp(&b.distance(target)));
+
+        for spot in candidates {
+            if spots.len() == count {
+                return spots;
+            }
+            if spots.iter().all(|s| s.distance(spot) >= WARP_SPACING) {
+                spots.push(spot);
+            }
+        }
+    }
+    spots
+}
+
+/// seconds before a warpgate can warp in again after warping in this
+pub const fn warp_cooldown(unit_type: UnitTypeId) -> f32 {
+    match unit_type {
+        UnitTypeId::Zealot | UnitTypeId::Adept => 20.0,
+        UnitTypeId::HighTemplar | UnitTypeId::DarkTemplar => 32.0,
+        _ => 23.0,
+    }
+}
+
+/// A warp-in we've asked a gate for, but haven't seen start yet
+#[derive(Debug, Clone, Copy)]
+struct WarpOrder {
+    unit_type: UnitTypeId,
+    ability: AbilityId,
+    issued: f32,
+}
+
+/// Remembers when each warpgate will be ready again, so we can warp in rounds
+#[derive(Debug, Default)]
+pub struct WarpGateTracker {
+    ready_at: HashMap<u64, f32>,
+    orders: HashMap<u64, WarpOrder>,
+}
+
+impl WarpGateTracker {
+    pub fn warped(&mut self, gate: u64, unit_type: UnitTypeId, now: f32) {
+        self.ready_at.insert(gate, now + warp_cooldown(unit_type));
+    }
+
+    /// The gate was told to warp in. It's busy until we know whether that worked.
+    pub fn ordered(&mut self, gate: u64, unit_type: UnitTypeId, ability: AbilityId, now: f32) {
+        let order = WarpOrder {
+            unit_type,
+            ability,
+            issued: now,
+        };
+        self.orders.insert(gate, order);
+    }
+
+    /// Puts gates whose warp-in started on cooldown, from when they were told to,
+    /// and forgets orders that never went through, so a failed command costs no cooldown.
+    /// `can_warp` says whether a gate still has the ability it was told to use.
+    pub fn confirm(&mut self, now: f32, can_warp: impl Fn(u64, AbilityId) -> bool) {
+        let orders: Vec<(u64, WarpOrder)> = self.orders.iter().map(|(g, o)| (*g, *o)).collect();
+        for (gate, order) in orders {
+            if !can_warp(gate, order.ability) {
+                self.warped(gate, order.unit_type, order.issued);
+                self.orders.remove(&gate);
+            } else if now - order.issued > ORDER_TIMEOUT {
+                self.orders.remove(&gate);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, gate: u64) {
+        self.ready_at.remove(&gate);
+        self.orders.remove(&gate);
+    }
+
+    /// seconds until it can warp again, zero if it can now
+    pub fn cooldown_left(&self, gate: u64, now: f32) -> f32 {
+        self.ready_at
+            .get(&gate)
+            .map_or(0.0, |ready| (ready - now).max(0.0))
+    }
+
+    pub fn is_ready(&self, gate: u64, now: f32) -> bool {
+        !self.orders.contains_key(&gate)
+            && self.ready_at.get(&gate).is_none_or(|ready| *ready <= now)
+    }
+
+    /// The gates that are ready, unless another one is about to be and we should wait for it.
+    pub fn batch(&self, gates: &[u64], now: f32) -> Vec<u64> {
+        let almost = gates.iter().any(|gate| {
+            self.ready_at
+                .get(gate)
+                .is_some_and(|ready| *ready > now && *ready - now <= BATCH_WINDOW)
+        });
+        if almost {
+            return Vec::new();
+        }
+        gates
+            .iter()
+            .copied()
+            .filter(|gate| self.is_ready(*gate, now))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `.` is open ground, `#` is cliff, row 0 is y 0
+    fn terrain(rows: &[&str]) -> WarpTerrain {
+        let width = rows[0].len();
+        let open: Vec<bool> = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| c == '.'))
+            .collect();
+        WarpTerrain::new(width, rows.len(), open.clone(), open)
+    }
+
+    #[test]
+    fn uses_the_matrix_nearest_the_target() {
+        let matrices = [
+            Matrix {
+                pos: Point2::new(5.5, 10.5),
+                radius: 6.5,
+            },
+            Matrix {
+                pos: Point2::new(30.5, 10.5),
+                radius: 6.5,
+            },
+        ];
+        let spots = plan_warp_ins(
+            &matrices,
+            Point2::new(38.0, 10.0),
+            4,
+            &[],
+            &WarpTerrain::open(40, 20),
+        );
+        assert_eq!(spots.len(), 4);
+        assert!(spots.iter().all(|s| s.distance(matrices[1].pos) < 6.5));
+        for (i, a) in spots.iter().enumerate() {
+            assert!(spots[i + 1..]
+                .iter()
+                .all(|b| a.distance(*b) >= WARP_SPACING));
+        }
+    }
+
+    #[test]
+    fn stays_on_the_right_side_of_the_cliff() {
+        // the pylon is on the high ground edge, the fight is below,
+        // and the ramp is way off at the bottom
+        let mut rows = vec![".........#.........................."; 25];
+        rows.extend(["...................................."; 3]);
+        let terrain = terrain(&rows);
+        let matrix = Matrix {
+            pos: Point2::new(8.5, 10.5),
+            radius: 6.5,
+        };
+        let target = Point2::new(20.0, 10.0);
+
+        let spots = plan_warp_ins(&[matrix], target, 20, &[], &terrain);
+        assert!(!spots.is_empty());
+        assert!(spots.iter().all(|s| s.x >= 10.0));
+
+        // with nowhere on the near side, nothing rather than the wrong side
+        let far = Matrix {
+            pos: Point2::new(4.5, 10.5),
+            radius: 4.5,
+        };
+        assert!(plan_warp_ins(&[far], target, 4, &[], &terrain).is_empty());
+    }
+
+    #[test]
+    fn keeps_out_of_range() {
+        let matrix = Matrix {
+            pos: Point2::new(20.5, 10.5),
+            radius: 6.5,
+        };
+        let cannon = Threat {
+            pos: Point2::new(30.0, 10.0),
+            range: 7.0 + 1.0,
+        };
+        let spots = plan_warp_ins(
+            &[matrix],
+            Point2::new(34.0, 10.0),
+            10,
+            &[cannon],
+            &WarpTerrain::open(40, 20),
+        );
+        assert!(!spots.is_empty());
+        assert!(spots
+            .iter()
+            .all(|s| s.distance(cannon.pos) > cannon.range + FIRE_MARGIN));
+    }
+
+    #[test]
+    fn gates_warp_in_rounds() {
+        let mut gates = WarpGateTracker::default();
+        // never seen gates are ready
+        assert_eq!(gates.batch(&[1, 2, 3], 100.0), vec![1, 2, 3]);
+
+        gates.warped(1, UnitTypeId::Stalker, 100.0);
+        gates.warped(2, UnitTypeId::Stalker, 100.0);
+        gates.warped(3, UnitTypeId::Zealot, 101.0);
+        assert!(gates.batch(&[1, 2, 3], 110.0).is_empty());
+
+        // 3 is ready at 121, but 1 and 2 are only a couple of seconds behind
+        assert!(gates.batch(&[1, 2, 3], 121.0).is_empty());
+        assert_eq!(gates.batch(&[1, 2, 3], 123.0), vec![1, 2, 3]);
+
+        gates.remove(2);
+        gates.warped(1, UnitTypeId::Stalker, 123.0);
+        assert_eq!(gates.batch(&[2, 3], 124.0), vec![2, 3]);
+    }
+
+    #[test]
+    fn cooldown_starts_when_the_warp_does() {
+        let mut gates = WarpGateTracker::default();
+        gates.ordered(
+            1,
+            UnitTypeId::Stalker,
+            AbilityId::WarpGateTrainStalker,
+            100.0,
+        );
+        gates.ordered(
+            2,
+            UnitTypeId::Stalker,
+            AbilityId::WarpGateTrainStalker,
+            100.0,
+        );
+        // waiting to hear back, so neither gets asked twice
+        assert!(gates.batch(&[1, 2], 100.0).is_empty());
+
+        // 1 lost its warp abilities, 2 still has them
+        let started = |gate, _| gate != 1;
+        gates.confirm(101.0, started);
+        assert!(!gates.is_ready(1, 101.0));
+        assert!(!gates.is_ready(2, 101.0));
+        assert!((gates.cooldown_left(1, 101.0) - 22.0).abs() < f32::EPSILON);
+
+        // 2's command never went through, so it's free again without a cooldown
+        gates.confirm(104.0, started);
+        assert!(gates.is_ready(2, 104.0));
+        assert_eq!(gates.cooldown_left(2, 104.0), 0.0);
+        assert!(gates.is_ready(1, 123.0));
+    }
+}